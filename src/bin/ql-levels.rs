@@ -0,0 +1,126 @@
+use std::{collections::HashMap, env, fs, process};
+
+use game::{
+    level::{Problem, Severity},
+    pack::{self, LevelPack},
+    solver::{solve, SolverConfig},
+};
+
+const DEFAULT_PATH: &str = "www/assets/levels.json";
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| DEFAULT_PATH.into());
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        }
+    };
+
+    let pack = match LevelPack::parse(&contents, &path) {
+        Ok(pack) => pack,
+        Err(e) => {
+            eprintln!("{}", pack::error_message(&path, &e));
+            process::exit(1);
+        }
+    };
+    match (&pack.author, &pack.version) {
+        (Some(author), Some(version)) => println!("{} {} by {}", pack.title, version, author),
+        (Some(author), None) => println!("{} by {}", pack.title, author),
+        (None, Some(version)) => println!("{} {}", pack.title, version),
+        (None, None) => println!("{}", pack.title),
+    }
+    println!();
+    let levels = pack.levels;
+
+    let mut problems = Vec::new();
+    let mut names = HashMap::new();
+
+    for (idx, level) in levels.iter().enumerate() {
+        if let Some(first) = names.insert(level.name.as_str(), idx) {
+            let problem = Problem {
+                severity: Severity::Error,
+                message: format!("duplicate name, same as level #{}", first),
+            };
+            problems.push((idx, problem));
+        }
+        problems.extend(level.problems().into_iter().map(|problem| (idx, problem)));
+    }
+
+    let name_width = levels
+        .iter()
+        .map(|l| l.name.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max(4);
+
+    println!(
+        "{:>3}  {:name_width$}  {:>5}  {:>8}  {:>8}  {:>8}  {:>8}",
+        "#",
+        "name",
+        "rings",
+        "energy",
+        "base",
+        "free",
+        "best",
+        name_width = name_width
+    );
+    let config = SolverConfig::default();
+    for (idx, level) in levels.iter().enumerate() {
+        let base_energy = level.rings.iter().map(|r| r.base_energy).sum::<f64>();
+        let best = match solve(level, &config) {
+            Some(solution) => {
+                let three_stars = level.star_thresholds()[2];
+                if solution.score < three_stars {
+                    problems.push((
+                        idx,
+                        Problem {
+                            severity: Severity::Warning,
+                            message: format!(
+                                "three stars need {}%, but the solver only gets {:.2}%",
+                                three_stars, solution.score
+                            ),
+                        },
+                    ));
+                }
+                format!("{:.2}%", solution.score)
+            }
+            None => "-".into(),
+        };
+        println!(
+            "{:>3}  {:name_width$}  {:>5}  {:>8.2}  {:>8.2}  {:>8.2}  {:>8}",
+            idx,
+            level.name,
+            level.rings.len(),
+            level.energy,
+            base_energy,
+            level.energy - base_energy,
+            best,
+            name_width = name_width
+        );
+    }
+
+    if !problems.is_empty() {
+        println!();
+    }
+    let mut errors = 0;
+    for (idx, problem) in &problems {
+        let severity = match problem.severity {
+            Severity::Error => {
+                errors += 1;
+                "error"
+            }
+            Severity::Warning => "warning",
+        };
+        println!(
+            "{}: level #{} ({}): {}",
+            severity, idx, levels[*idx].name, problem.message
+        );
+    }
+
+    if errors != 0 {
+        process::exit(1);
+    }
+}
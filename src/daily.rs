@@ -0,0 +1,193 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    generator::{self, Difficulty},
+    level::GameLevel,
+};
+
+// keeps the daily levels apart from the endless run of the same day
+const DAILY_STREAM: u64 = 0xda11 << 48;
+
+// how many of the last days are shown in the shared text
+const SHARED_DAYS: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn new(year: u32, month: u32, day: u32) -> Self {
+        Self { year, month, day }
+    }
+
+    fn is_leap_year(year: u32) -> bool {
+        match (year % 4, year % 100, year % 400) {
+            (_, _, 0) => true,
+            (_, 0, _) => false,
+            (0, _, _) => true,
+            _ => false,
+        }
+    }
+
+    fn days_in_month(year: u32, month: u32) -> u32 {
+        match month {
+            2 if Self::is_leap_year(year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        match (self.month, self.day) {
+            (1, 1) => Self::new(self.year - 1, 12, 31),
+            (month, 1) => Self::new(
+                self.year,
+                month - 1,
+                Self::days_in_month(self.year, month - 1),
+            ),
+            (month, day) => Self::new(self.year, month, day - 1),
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The same level for everyone on the given day
+pub fn daily_level(date: Date) -> GameLevel {
+    let seed = generator::daily_seed(date.year, date.month, date.day);
+    let mut level = generator::seeded_level(seed ^ DAILY_STREAM, Difficulty::Normal);
+    level.name = format!("Daily {}", date);
+    level
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyRecord {
+    /// The score of every day played, keyed by the date, `None` when it was lost
+    pub history: BTreeMap<String, Option<f64>>,
+    /// Days won in a row, up to the last one won
+    pub streak: u32,
+    pub best_streak: u32,
+}
+
+impl DailyRecord {
+    pub fn played(&self, date: Date) -> bool {
+        self.history.contains_key(&date.to_string())
+    }
+
+    pub fn score(&self, date: Date) -> Option<f64> {
+        self.history.get(&date.to_string()).copied().flatten()
+    }
+
+    /// Uses up the attempt of the day, it counts as lost until it is won
+    pub fn start(&mut self, date: Date) {
+        self.history.insert(date.to_string(), None);
+    }
+
+    pub fn finish(&mut self, date: Date, score: f64) {
+        self.history.insert(date.to_string(), Some(score));
+        self.streak = if self.score(date.previous()).is_some() {
+            self.streak + 1
+        } else {
+            1
+        };
+        self.best_streak = self.best_streak.max(self.streak);
+    }
+
+    /// The stored streak is only updated on wins, so it might be broken since
+    pub fn current_streak(&self, today: Date) -> u32 {
+        match self.history.get(&today.to_string()) {
+            Some(Some(_)) => self.streak,
+            Some(None) => 0,
+            None if self.score(today.previous()).is_some() => self.streak,
+            None => 0,
+        }
+    }
+
+    /// A spoiler-free summary of the day to paste somewhere
+    pub fn share_text(&self, date: Date) -> String {
+        let bar = match self.score(date) {
+            Some(score) => {
+                let filled = (score / 10.0).floor() as usize;
+                format!(
+                    "{}{} {:.2}%",
+                    "🟩".repeat(filled),
+                    "⬜".repeat(10 - filled.min(10)),
+                    score
+                )
+            }
+            None => format!("{} lost", "🟥".repeat(10)),
+        };
+        let mut days = Vec::new();
+        let mut day = date;
+        for _ in 0..SHARED_DAYS {
+            days.push(match self.history.get(&day.to_string()) {
+                Some(Some(_)) => "🟩",
+                Some(None) => "🟥",
+                None => "⬜",
+            });
+            day = day.previous();
+        }
+        days.reverse();
+        format!(
+            "Quantum Loops daily {}\n{}\n{} streak: {}",
+            date,
+            bar,
+            days.concat(),
+            self.current_streak(date)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previous_days() {
+        assert_eq!(Date::new(2021, 3, 1).previous(), Date::new(2021, 2, 28));
+        assert_eq!(Date::new(2020, 3, 1).previous(), Date::new(2020, 2, 29));
+        assert_eq!(Date::new(2021, 1, 1).previous(), Date::new(2020, 12, 31));
+        assert_eq!(Date::new(2021, 5, 12).previous(), Date::new(2021, 5, 11));
+    }
+
+    #[test]
+    fn streaks() {
+        let mut record = DailyRecord::default();
+        let first = Date::new(2020, 12, 30);
+        let second = Date::new(2020, 12, 31);
+        let third = Date::new(2021, 1, 1);
+
+        record.start(first);
+        record.finish(first, 80.0);
+        record.start(second);
+        record.finish(second, 90.0);
+        assert_eq!(record.streak, 2);
+        assert_eq!(record.current_streak(third), 2);
+
+        record.start(third);
+        assert_eq!(record.current_streak(third), 0);
+        assert_eq!(record.best_streak, 2);
+        assert!(record
+            .share_text(third)
+            .ends_with("⬜⬜⬜⬜🟩🟩🟥 streak: 0"));
+    }
+
+    #[test]
+    fn same_level_for_the_same_day() {
+        let date = Date::new(2020, 10, 3);
+        let a = serde_json::to_string(&daily_level(date)).unwrap();
+        let b = serde_json::to_string(&daily_level(date)).unwrap();
+        assert_eq!(a, b);
+    }
+}
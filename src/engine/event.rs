@@ -1,4 +1,5 @@
 use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::{prelude::*, *};
 use web_sys::{EventTarget, MouseEvent, TouchEvent, WheelEvent};
 
@@ -128,7 +129,7 @@ pub(super) fn setup_touch_events(target: &EventTarget, events: Mut<Vec<Event>>)
     });
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MouseButton {
     Left,
     Middle,
@@ -170,7 +171,7 @@ impl MouseButton {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyMeta {
     repeat: bool,
     alt: bool,
@@ -179,7 +180,7 @@ pub struct KeyMeta {
     meta: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
     MouseDown {
         pos: Vector2<f64>,
@@ -2,7 +2,7 @@
 
 use std::fmt::Debug;
 
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use wasm_bindgen::{prelude::*, *};
 use web_sys::{Document, HtmlElement, Window};
 
@@ -12,14 +12,20 @@ use sprite::Spritesheet;
 use util::Mut;
 
 use crate::engine::surface::Surface;
+use profiles::Profiles;
 use std::cell::{Ref, RefMut};
+use storage::{DataStore, StorageBackend};
+use versioned::Versioned;
 
 pub mod event;
+pub mod profiles;
 pub mod sound;
 pub mod sprite;
+pub mod storage;
 pub mod surface;
 pub mod ui;
 pub mod util;
+pub mod versioned;
 
 pub fn window() -> Window {
     web_sys::window().expect("No window")
@@ -37,24 +43,99 @@ pub fn time() -> f64 {
     js_sys::Date::now() / 1e3
 }
 
-fn get_data<D: Default + for<'a> Deserialize<'a>>() -> D {
-    window()
-        .local_storage()
-        .unwrap()
-        .unwrap()
-        .get("data")
-        .unwrap()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_default()
+/// The local date as the year, month and day of the month
+pub fn today() -> (u32, u32, u32) {
+    let date = js_sys::Date::new_0();
+    (date.get_full_year(), date.get_month() + 1, date.get_date())
 }
 
-fn set_data<D: Serialize>(data: &D) {
-    window()
-        .local_storage()
-        .unwrap()
-        .unwrap()
-        .set("data", &serde_json::to_string(data).unwrap())
-        .unwrap()
+/// Makes the browser save the given text as a file
+pub fn download(file_name: &str, contents: &str) -> Result<(), JsValue> {
+    let href = format!(
+        "data:application/json;charset=utf-8,{}",
+        String::from(js_sys::encode_uri_component(contents))
+    );
+    let link = document().create_element("a")?;
+    link.set_attribute("href", &href)?;
+    link.set_attribute("download", file_name)?;
+    link.dyn_into::<HtmlElement>()?.click();
+    Ok(())
+}
+
+/// `navigator.clipboard` is behind the unstable APIs of web-sys, so it is reached by reflection
+pub fn copy_to_clipboard(text: &str) -> Result<(), JsValue> {
+    let navigator = js_sys::Reflect::get(&window(), &"navigator".into())?;
+    let clipboard = js_sys::Reflect::get(&navigator, &"clipboard".into())?;
+    let write_text: js_sys::Function =
+        js_sys::Reflect::get(&clipboard, &"writeText".into())?.dyn_into()?;
+    write_text.call1(&clipboard, &text.into())?;
+    Ok(())
+}
+
+pub const FIXED_TIMESTEP: f64 = 1.0 / 60.0;
+
+// so that a backgrounded tab does not try to catch up on minutes of ticks
+const MAX_FRAME_TIME: f64 = 0.25;
+
+/// Splits the frame times into the fixed updates, carrying what is left over to the next frame
+#[derive(Debug, Default)]
+struct FixedSteps {
+    accumulator: f64,
+}
+
+impl FixedSteps {
+    /// Adds the time since the last frame and returns it clamped to `MAX_FRAME_TIME`
+    fn add_frame(&mut self, elapsed: f64) -> f64 {
+        let frame_time = elapsed.min(MAX_FRAME_TIME);
+        self.accumulator += frame_time;
+        frame_time
+    }
+
+    /// Takes the time of one fixed update, if there is enough of it accumulated
+    fn next_step(&mut self) -> bool {
+        if self.accumulator >= FIXED_TIMESTEP {
+            self.accumulator -= FIXED_TIMESTEP;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How far between the last and the next fixed update it is, from 0 to 1,
+    /// only makes sense once all of the due steps are taken
+    fn alpha(&self) -> f64 {
+        self.accumulator / FIXED_TIMESTEP
+    }
+}
+
+fn get_data<D: Default + Versioned>(store: &mut DataStore, key: &str) -> D {
+    let blob = match store.get(key) {
+        Some(blob) => blob,
+        None => return D::default(),
+    };
+    match versioned::load(&blob) {
+        Ok(data) => data,
+        Err(e) => {
+            // never just drop the progress, someone might be able to recover it
+            let backup_key = format!("{}.backup", key);
+            log::error!(
+                "Failed to load stored data ({}), the original is kept under '{}'",
+                e,
+                backup_key
+            );
+            store.set(&backup_key, &blob);
+            D::default()
+        }
+    }
+}
+
+fn set_data<D: Serialize>(store: &mut DataStore, key: &str, data: &D) {
+    match serde_json::to_string(data) {
+        Ok(json) => {
+            store.set(key, &json);
+        }
+        Err(e) => log::error!("Failed to serialize the data: {}", e),
+    }
 }
 
 fn compute_rem_to_pixel_ratio() -> f64 {
@@ -102,18 +183,27 @@ impl<G: Game> StateTransition<G> {
 
 pub struct Context<'a, G: Game> {
     delta_time: f64,
+    alpha: f64,
     rem_to_px: f64,
     surface: Mut<Surface>,
     sound_context: Mut<SoundContext>,
+    data_store: Mut<DataStore>,
+    profiles: &'a mut Profiles,
     storage: &'a mut G::Storage,
     pub game: &'a mut G,
 }
 
 impl<'a, G: Game> Context<'a, G> {
+    /// The fixed timestep in `on_fixed_update` and the frame time in `on_render`
     pub fn delta_time(&self) -> f64 {
         self.delta_time
     }
 
+    /// How far between the last and the next fixed update the frame is being rendered, from 0 to 1
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
     pub fn rem_to_px(&self, rem: f64) -> f64 {
         rem * self.rem_to_px
     }
@@ -131,9 +221,62 @@ impl<'a, G: Game> Context<'a, G> {
     }
 
     pub fn set_storage(&mut self, new_storage: G::Storage) {
-        set_data(&new_storage);
+        let key = Profiles::data_key(self.profiles.active());
+        set_data(&mut self.data_store.borrow_mut(), &key, &new_storage);
         *self.storage = new_storage;
     }
+
+    pub fn profiles(&self) -> &Profiles {
+        self.profiles
+    }
+
+    /// Makes the given profile the active one, `storage` then returns its data
+    pub fn switch_profile(&mut self, name: &str) -> bool {
+        if !self.profiles.set_active(name) {
+            return false;
+        }
+        let mut data_store = self.data_store.borrow_mut();
+        self.profiles.save(&mut data_store);
+        *self.storage = get_data(&mut data_store, &Profiles::data_key(name));
+        true
+    }
+
+    /// Creates a profile with the default data, without switching to it
+    pub fn create_profile(&mut self, name: &str) -> bool {
+        if !self.profiles.add(name) {
+            return false;
+        }
+        let mut data_store = self.data_store.borrow_mut();
+        set_data(
+            &mut data_store,
+            &Profiles::data_key(name.trim()),
+            &G::Storage::default(),
+        );
+        self.profiles.save(&mut data_store);
+        true
+    }
+
+    /// Deletes the profile along with its data, the last profile can not be deleted
+    pub fn delete_profile(&mut self, name: &str) -> bool {
+        let was_active = self.profiles.active() == name;
+        if !self.profiles.remove(name) {
+            return false;
+        }
+        let mut data_store = self.data_store.borrow_mut();
+        data_store.remove(&Profiles::data_key(name));
+        self.profiles.save(&mut data_store);
+        if was_active {
+            *self.storage = get_data(&mut data_store, &Profiles::data_key(self.profiles.active()));
+        }
+        true
+    }
+
+    /// Whether the progress survives a page reload, it does not
+    /// when the browser has storage disabled or the last write failed
+    pub fn is_storage_persistent(&self) -> bool {
+        let data_store = self.data_store.borrow();
+        data_store.is_persistent() && data_store.last_error().is_none()
+    }
 }
 
 fn handle_transition<G: Game>(
@@ -173,12 +316,19 @@ fn run<G: Game>() {
 
     let surface = Mut::new(Surface::new(event_queue.clone()));
     let sound_context = Mut::new(SoundContext::new());
+    let data_store = Mut::new(DataStore::new(storage::default_backend(), G::NAMESPACE));
 
     let (mut game, current_state) = G::load(Resources {
         surface: surface.clone(),
         sound_context: sound_context.clone(),
+        data_store: data_store.clone(),
     });
-    let mut storage = get_data();
+    let (mut profiles, mut storage) = {
+        let mut data_store = data_store.borrow_mut();
+        let profiles = Profiles::load(&mut data_store);
+        let storage = get_data(&mut data_store, &Profiles::data_key(profiles.active()));
+        (profiles, storage)
+    };
 
     let mut states = vec![current_state];
     handle_transition(
@@ -186,15 +336,19 @@ fn run<G: Game>() {
         |state, context| state.on_pushed(context),
         Context {
             delta_time: 0.0,
+            alpha: 0.0,
             rem_to_px: compute_rem_to_pixel_ratio(),
             surface: surface.clone(),
             sound_context: sound_context.clone(),
+            data_store: data_store.clone(),
             game: &mut game,
+            profiles: &mut profiles,
             storage: &mut storage,
         },
     );
 
     let mut last_time = time();
+    let mut steps = FixedSteps::default();
 
     let window_moved = window();
 
@@ -210,6 +364,7 @@ fn run<G: Game>() {
             .unwrap();
 
         let now = time();
+        let frame_time = steps.add_frame(now - last_time);
 
         handle_transition(
             &mut states,
@@ -219,16 +374,27 @@ fn run<G: Game>() {
                         StateTransition::None => (),
                         x => break x,
                     }
+                } else if steps.next_step() {
+                    context.delta_time = FIXED_TIMESTEP;
+                    match state.on_fixed_update(context) {
+                        StateTransition::None => (),
+                        x => break x,
+                    }
                 } else {
-                    break state.on_update(context);
+                    context.delta_time = frame_time;
+                    context.alpha = steps.alpha();
+                    break state.on_render(context);
                 }
             },
             Context {
-                delta_time: now - last_time,
+                delta_time: frame_time,
+                alpha: 0.0,
                 rem_to_px: compute_rem_to_pixel_ratio(),
                 surface: surface.clone(),
                 sound_context: sound_context.clone(),
+                data_store: data_store.clone(),
                 game: &mut game,
+                profiles: &mut profiles,
                 storage: &mut storage,
             },
         );
@@ -248,6 +414,7 @@ fn run<G: Game>() {
 pub struct Resources {
     surface: Mut<Surface>,
     sound_context: Mut<SoundContext>,
+    data_store: Mut<DataStore>,
 }
 
 impl Resources {
@@ -258,6 +425,11 @@ impl Resources {
     pub fn load_sound(&self, url: &str) -> Sound {
         Sound::load(self.sound_context.clone(), url)
     }
+
+    /// Replaces the default storage backend, the stored data is only read after the game is loaded
+    pub fn set_storage_backend(&self, backend: Box<dyn StorageBackend>) {
+        self.data_store.borrow_mut().set_backend(backend);
+    }
 }
 
 // copying Amethyst so hard accidentaly
@@ -274,7 +446,11 @@ where
         StateTransition::None
     }
 
-    fn on_update(&mut self, _context: &mut Context<G>) -> StateTransition<G> {
+    fn on_fixed_update(&mut self, _context: &mut Context<G>) -> StateTransition<G> {
+        StateTransition::None
+    }
+
+    fn on_render(&mut self, _context: &mut Context<G>) -> StateTransition<G> {
         StateTransition::None
     }
 
@@ -287,7 +463,10 @@ pub trait Game
 where
     Self: Debug + Sized + 'static,
 {
-    type Storage: Clone + Default + Serialize + for<'a> Deserialize<'a>;
+    type Storage: Clone + Default + Versioned;
+
+    /// Prefix for all the keys this game stores
+    const NAMESPACE: &'static str;
 
     fn load(resources: Resources) -> (Self, Box<dyn GameState<Self>>);
 }
@@ -305,3 +484,38 @@ mod private {
 impl<G: Game> private::Sealed for G {}
 
 impl<G: Game + private::Sealed> GameRun for G {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_steps(steps: &mut FixedSteps) -> usize {
+        let mut count = 0;
+        while steps.next_step() {
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn frames_are_split_into_fixed_steps() {
+        let mut steps = FixedSteps::default();
+        assert_eq!(steps.add_frame(0.01), 0.01);
+        assert_eq!(take_steps(&mut steps), 0);
+        assert!((steps.alpha() - 0.6).abs() < 1e-9);
+
+        // the leftover from the last frame counts towards this one
+        steps.add_frame(0.03);
+        assert_eq!(take_steps(&mut steps), 2);
+        assert!((steps.alpha() - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut steps = FixedSteps::default();
+        assert_eq!(steps.add_frame(60.0), MAX_FRAME_TIME);
+        let count = take_steps(&mut steps);
+        assert_eq!(count, (MAX_FRAME_TIME / FIXED_TIMESTEP).round() as usize);
+        assert!((0.0..1.0).contains(&steps.alpha()));
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::storage::DataStore;
+
+pub const DEFAULT_PROFILE: &str = "Player";
+pub const MAX_NAME_LENGTH: usize = 16;
+
+const PROFILES_KEY: &str = "profiles";
+// where the data lived before there were profiles
+const LEGACY_DATA_KEY: &str = "data";
+
+/// The names of all the profiles stored side by side, the data of each one
+/// lives under its own key so that a broken profile does not affect the others
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profiles {
+    active: String,
+    names: Vec<String>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.into(),
+            names: vec![DEFAULT_PROFILE.into()],
+        }
+    }
+}
+
+impl Profiles {
+    pub fn load(store: &mut DataStore) -> Self {
+        let stored = store
+            .get(PROFILES_KEY)
+            .and_then(|json| match serde_json::from_str::<Profiles>(&json) {
+                Ok(profiles) => Some(profiles),
+                Err(e) => {
+                    log::error!("Failed to load the profiles ({}), using the default", e);
+                    None
+                }
+            })
+            .filter(|profiles| profiles.names.contains(&profiles.active));
+
+        match stored {
+            Some(profiles) => profiles,
+            None => {
+                let profiles = Self::default();
+                // the single player from before becomes the default profile
+                let legacy = store
+                    .get(LEGACY_DATA_KEY)
+                    .or_else(|| store.get_legacy(LEGACY_DATA_KEY));
+                if let Some(blob) = legacy {
+                    if store.get(&Self::data_key(DEFAULT_PROFILE)).is_none() {
+                        store.set(&Self::data_key(DEFAULT_PROFILE), &blob);
+                    }
+                }
+                profiles.save(store);
+                profiles
+            }
+        }
+    }
+
+    pub fn save(&self, store: &mut DataStore) {
+        match serde_json::to_string(self) {
+            Ok(json) => {
+                store.set(PROFILES_KEY, &json);
+            }
+            Err(e) => log::error!("Failed to serialize the profiles: {}", e),
+        }
+    }
+
+    pub fn data_key(name: &str) -> String {
+        format!("profile.{}", name)
+    }
+
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+    }
+
+    pub fn is_valid_name(&self, name: &str) -> bool {
+        let name = name.trim();
+        !name.is_empty() && name.chars().count() <= MAX_NAME_LENGTH && !self.contains(name)
+    }
+
+    pub(super) fn add(&mut self, name: &str) -> bool {
+        if !self.is_valid_name(name) {
+            return false;
+        }
+        self.names.push(name.trim().into());
+        true
+    }
+
+    /// The last profile can not be removed, the active one switches to the first one left
+    pub(super) fn remove(&mut self, name: &str) -> bool {
+        if self.names.len() <= 1 || !self.contains(name) {
+            return false;
+        }
+        self.names.retain(|n| n != name);
+        if self.active == name {
+            self.active = self.names[0].clone();
+        }
+        true
+    }
+
+    pub(super) fn set_active(&mut self, name: &str) -> bool {
+        if !self.contains(name) {
+            return false;
+        }
+        self.active = name.into();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::storage::MemoryStorage;
+
+    #[test]
+    fn legacy_data_becomes_the_default_profile() {
+        let mut store = DataStore::new(Box::new(MemoryStorage::new()), "game");
+        store.set(LEGACY_DATA_KEY, "{}");
+
+        let profiles = Profiles::load(&mut store);
+        assert_eq!(profiles.active(), DEFAULT_PROFILE);
+        assert_eq!(
+            store.get(&Profiles::data_key(DEFAULT_PROFILE)).as_deref(),
+            Some("{}")
+        );
+        assert!(store.get(PROFILES_KEY).is_some());
+    }
+
+    #[test]
+    fn last_profile_is_kept() {
+        let mut profiles = Profiles::default();
+        assert!(profiles.add("Second"));
+        assert!(!profiles.add(" Second "));
+        assert!(profiles.remove(DEFAULT_PROFILE));
+        assert_eq!(profiles.active(), "Second");
+        assert!(!profiles.remove("Second"));
+    }
+}
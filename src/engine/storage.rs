@@ -0,0 +1,275 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter},
+};
+
+use wasm_bindgen::JsValue;
+
+use crate::engine::window;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Unavailable(String),
+    Read(String),
+    Write(String),
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            StorageError::Unavailable(e) => write!(f, "storage is unavailable: {}", e),
+            StorageError::Read(e) => write!(f, "failed to read: {}", e),
+            StorageError::Write(e) => write!(f, "failed to write: {}", e),
+        }
+    }
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
+}
+
+pub trait StorageBackend: Debug {
+    fn get(&self, key: &str) -> Result<Option<String>, StorageError>;
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError>;
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError>;
+
+    /// Whether the data survives a page reload
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
+
+pub struct LocalStorage {
+    storage: web_sys::Storage,
+}
+
+impl Debug for LocalStorage {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str("LocalStorage")
+    }
+}
+
+impl LocalStorage {
+    pub fn new() -> Result<Self, StorageError> {
+        // this throws (instead of returning null) in some sandboxed iframes
+        match window().local_storage() {
+            Ok(Some(storage)) => Ok(Self { storage }),
+            Ok(None) => Err(StorageError::Unavailable("no localStorage".into())),
+            Err(e) => Err(StorageError::Unavailable(js_error(e))),
+        }
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn get(&self, key: &str) -> Result<Option<String>, StorageError> {
+        self.storage
+            .get(key)
+            .map_err(|e| StorageError::Read(js_error(e)))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError> {
+        self.storage
+            .set(key, value)
+            .map_err(|e| StorageError::Write(js_error(e)))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        self.storage
+            .remove_item(key)
+            .map_err(|e| StorageError::Write(js_error(e)))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: HashMap<String, String>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn get(&self, key: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError> {
+        self.entries.insert(key.into(), value.into());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn is_persistent(&self) -> bool {
+        false
+    }
+}
+
+/// Stores every key as a separate file in the given directory
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct FileStorage {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Result<Self, StorageError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| StorageError::Unavailable(e.to_string()))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> std::path::PathBuf {
+        let name = key
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        self.dir.join(name)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageBackend for FileStorage {
+    fn get(&self, key: &str) -> Result<Option<String>, StorageError> {
+        match std::fs::read_to_string(self.path(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StorageError::Read(e.to_string())),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError> {
+        std::fs::write(self.path(key), value).map_err(|e| StorageError::Write(e.to_string()))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        match std::fs::remove_file(self.path(key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(StorageError::Write(e.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// localStorage when the browser allows it, otherwise the progress only lives until a reload
+pub fn default_backend() -> Box<dyn StorageBackend> {
+    match LocalStorage::new() {
+        Ok(storage) => Box::new(storage),
+        Err(e) => {
+            log::warn!("{}, progress will not be saved", e);
+            Box::new(MemoryStorage::new())
+        }
+    }
+}
+
+/// The backend together with the key namespace of the game,
+/// remembers the last error so that it can be shown to the player
+#[derive(Debug)]
+pub struct DataStore {
+    backend: Box<dyn StorageBackend>,
+    namespace: &'static str,
+    last_error: Option<StorageError>,
+}
+
+impl DataStore {
+    pub fn new(backend: Box<dyn StorageBackend>, namespace: &'static str) -> Self {
+        Self {
+            backend,
+            namespace,
+            last_error: None,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Box<dyn StorageBackend>) {
+        self.backend = backend;
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.backend.is_persistent()
+    }
+
+    pub fn last_error(&self) -> Option<&StorageError> {
+        self.last_error.as_ref()
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}.{}", self.namespace, key)
+    }
+
+    fn track<T>(&mut self, result: Result<T, StorageError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::error!("{}", e);
+                self.last_error = Some(e);
+                None
+            }
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        let result = self.backend.get(&self.key(key));
+        self.track(result).flatten()
+    }
+
+    /// Reads a key which is not namespaced, for the data saved by older versions
+    pub fn get_legacy(&mut self, key: &str) -> Option<String> {
+        let result = self.backend.get(key);
+        self.track(result).flatten()
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let result = self.backend.set(&self.key(key), value);
+        self.track(result).is_some()
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        let result = self.backend.remove(&self.key(key));
+        self.track(result).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_namespaced() {
+        let mut store = DataStore::new(Box::new(MemoryStorage::new()), "game");
+        assert!(store.set("data", "1"));
+        assert_eq!(store.get("data").as_deref(), Some("1"));
+        assert_eq!(store.get_legacy("game.data").as_deref(), Some("1"));
+        assert_eq!(store.get_legacy("data"), None);
+        assert!(store.remove("data"));
+        assert_eq!(store.get("data"), None);
+        assert!(!store.is_persistent());
+    }
+
+    #[test]
+    fn file_storage_roundtrips() {
+        let dir = std::env::temp_dir().join(format!("storage-test-{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir).unwrap();
+        storage.set("a/b", "value").unwrap();
+        assert_eq!(storage.get("a/b").unwrap().as_deref(), Some("value"));
+        storage.remove("a/b").unwrap();
+        assert_eq!(storage.get("a/b").unwrap(), None);
+        storage.remove("a/b").unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
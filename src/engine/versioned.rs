@@ -0,0 +1,78 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+pub const VERSION_FIELD: &str = "version";
+
+/// Turns the data of some version into the data of the next one
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Migration at index `i` upgrades the data from version `i` to `i + 1`,
+    /// data without a version field is considered to be of version 0
+    const MIGRATIONS: &'static [Migration];
+
+    fn current_version() -> u64 {
+        Self::MIGRATIONS.len() as u64
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Json(serde_json::Error),
+    NotAnObject,
+    TooNew(u64),
+    Migration { from: u64, message: String },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Json(e) => write!(f, "{}", e),
+            LoadError::NotAnObject => write!(f, "stored data is not an object"),
+            LoadError::TooNew(version) => write!(f, "unknown data version {}", version),
+            LoadError::Migration { from, message } => write!(
+                f,
+                "migration from version {} to {} failed: {}",
+                from,
+                from + 1,
+                message
+            ),
+        }
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(e: serde_json::Error) -> Self {
+        LoadError::Json(e)
+    }
+}
+
+pub fn migrate<D: Versioned>(mut value: Value) -> Result<Value, LoadError> {
+    let version = match value.as_object() {
+        Some(object) => object
+            .get(VERSION_FIELD)
+            .and_then(Value::as_u64)
+            .unwrap_or(0),
+        None => return Err(LoadError::NotAnObject),
+    };
+    if version > D::current_version() {
+        return Err(LoadError::TooNew(version));
+    }
+    for (from, migration) in D::MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut value).map_err(|message| LoadError::Migration {
+            from: from as u64,
+            message,
+        })?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert(VERSION_FIELD.into(), (from as u64 + 1).into());
+        }
+    }
+    Ok(value)
+}
+
+pub fn load<D: Versioned>(json: &str) -> Result<D, LoadError> {
+    let value = migrate::<D>(serde_json::from_str(json)?)?;
+    Ok(serde_json::from_value(value)?)
+}
@@ -0,0 +1,330 @@
+use std::{collections::HashSet, ops::Range};
+
+use noise::{NoiseFn, Perlin, Seedable};
+
+use crate::{
+    level::{EnergyRing, GameLevel},
+    solver::{solve, SolverConfig},
+};
+
+// how many random levels are tried for a single seed before giving up
+const MAX_ATTEMPTS: usize = 32;
+// and how many seeds before going for an easier difficulty,
+// the levels are generated on the spot, so it can not take forever
+const MAX_SEEDS: usize = 8;
+
+const MIN_RADIUS: f64 = 0.08;
+const MAX_RADIUS: f64 = 0.42;
+// so that the solver (and the player) can cut a ring without touching the neighbours
+const MIN_GAP: f64 = 0.05;
+
+const COLORS: &[&str] = &[
+    "black",
+    "purple",
+    "green",
+    "lightblue",
+    "blue",
+    "red",
+    "#4f2b59",
+    "#79b551",
+];
+
+/// A splitmix64 generator, unlike the ones from `rand` its sequence
+/// is never going to change, so a seed always gives the same levels
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn range(&mut self, range: Range<f64>) -> f64 {
+        range.start + (range.end - range.start) * self.next_f64()
+    }
+
+    pub fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Endless runs get harder the further the player gets
+    pub fn for_depth(depth: u32) -> Self {
+        match depth {
+            0..=2 => Difficulty::Easy,
+            3..=7 => Difficulty::Normal,
+            _ => Difficulty::Hard,
+        }
+    }
+
+    /// The efficiency the solver has to end up with, the lower it is
+    /// the less energy there is to spare for any mistakes
+    pub fn efficiency_band(self) -> Range<f64> {
+        match self {
+            Difficulty::Easy => 88.0..96.0,
+            Difficulty::Normal => 78.0..88.0,
+            Difficulty::Hard => 65.0..78.0,
+        }
+    }
+
+    /// The next easier one, `None` for the easiest
+    pub fn easier(self) -> Option<Self> {
+        match self {
+            Difficulty::Easy => None,
+            Difficulty::Normal => Some(Difficulty::Easy),
+            Difficulty::Hard => Some(Difficulty::Normal),
+        }
+    }
+
+    fn ring_counts(self) -> Range<usize> {
+        match self {
+            Difficulty::Easy => 1..3,
+            Difficulty::Normal => 2..5,
+            Difficulty::Hard => 3..6,
+        }
+    }
+
+    fn restore_times(self) -> Range<f64> {
+        match self {
+            Difficulty::Easy => 5.0..9.0,
+            Difficulty::Normal => 3.0..6.0,
+            Difficulty::Hard => 1.5..4.0,
+        }
+    }
+
+    fn max_offset(self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.0,
+            Difficulty::Normal => 0.03,
+            Difficulty::Hard => 0.06,
+        }
+    }
+}
+
+/// The player the levels are tuned for, slower and sloppier than the solver defaults
+pub fn player() -> SolverConfig {
+    SolverConfig {
+        cut_length: 30.0,
+        cut_duration: 0.15,
+        cut_interval: 0.6,
+        ..SolverConfig::default()
+    }
+}
+
+pub fn daily_seed(year: u32, month: u32, day: u32) -> u64 {
+    (year * 10000 + month * 100 + day) as u64
+}
+
+fn random_level(
+    rng: &mut Rng,
+    noise: &Perlin,
+    attempt: usize,
+    difficulty: Difficulty,
+) -> GameLevel {
+    let counts = difficulty.ring_counts();
+    let count = counts.start + rng.index(counts.end - counts.start);
+
+    // every ring gets its own band of radii, so that there is always a gap between them
+    let band = (MAX_RADIUS - MIN_RADIUS) / count as f64;
+    let mut base_energies = HashSet::new();
+    let rings = (0..count)
+        .map(|idx| {
+            let radius = MIN_RADIUS + band * idx as f64 + rng.range(0.0..band - MIN_GAP);
+
+            // equal energies would make the order of the rings ambiguous
+            let mut base_energy = rng.range(5.0..40.0).round();
+            while !base_energies.insert(base_energy as i64) {
+                base_energy += 1.0;
+            }
+
+            let t = attempt as f64 * 10.0 + idx as f64 * 0.37;
+            let offset = [noise.get([t, 0.5]), noise.get([0.5, t])];
+            let max_offset = difficulty.max_offset();
+
+            let radius = (radius * 1000.0).round() / 1000.0;
+            let offset = [
+                (offset[0] * max_offset).clamp(-max_offset, max_offset),
+                (offset[1] * max_offset).clamp(-max_offset, max_offset),
+            ];
+            EnergyRing {
+                width: rng.range(1.0..4.0).round(),
+                color: COLORS[rng.index(COLORS.len())].into(),
+                restore_time: rng.range(difficulty.restore_times()).round(),
+                ..EnergyRing::new(offset.into(), radius, base_energy)
+            }
+        })
+        .collect();
+
+    GameLevel::new(String::new(), 0.0, rings)
+}
+
+// sets the energy so that the player ends up with an efficiency from the band
+fn tune_energy(
+    mut level: GameLevel,
+    rng: &mut Rng,
+    difficulty: Difficulty,
+    config: &SolverConfig,
+) -> Option<GameLevel> {
+    let base = level.rings.iter().map(|r| r.base_energy).sum::<f64>();
+
+    // plenty of energy first, only to find out how much the player has to spend
+    level.energy = base * 10.0;
+    let spent = solve(&level, config)?.energy_spent;
+
+    let band = difficulty.efficiency_band();
+    let target = rng.range(band.clone()) / 100.0;
+    // the efficiency is (energy - spent) / (energy - base), solved for the energy
+    level.energy = ((spent - target * base) / (1.0 - target)).ceil();
+
+    // and then the whole level is played once more to make sure it holds
+    let score = solve(&level, config)?.score;
+    if !band.contains(&score) {
+        return None;
+    }
+    // relative to the tuned player, who is already far from perfect
+    level.stars = Some([
+        (score * 0.5).round(),
+        (score * 0.75).round(),
+        (score * 0.9).round(),
+    ]);
+    Some(level)
+}
+
+/// A level the solver wins with an efficiency inside the band of the difficulty,
+/// `None` when none of the attempts with this seed worked out
+pub fn generate(seed: u64, difficulty: Difficulty) -> Option<GameLevel> {
+    let mut rng = Rng::new(seed);
+    let noise = Perlin::new().set_seed(seed as u32);
+    let config = player();
+    (0..MAX_ATTEMPTS).find_map(|attempt| {
+        let level = random_level(&mut rng, &noise, attempt, difficulty);
+        tune_energy(level, &mut rng, difficulty, &config)
+    })
+}
+
+// two rings with plenty of energy, for when not even the easy levels work out
+fn fallback_level() -> GameLevel {
+    let rings = vec![
+        EnergyRing::new([0.0, 0.0].into(), 0.15, 10.0),
+        EnergyRing::new([0.0, 0.0].into(), 0.3, 20.0),
+    ];
+    GameLevel::new(String::new(), 60.0, rings)
+}
+
+// tries the seeds that follow from the given one until a level works out,
+// then the easier difficulties, and a fixed level in the end
+fn first_generated(
+    seed: u64,
+    difficulty: Difficulty,
+    generate: impl Fn(u64, Difficulty) -> Option<GameLevel>,
+) -> GameLevel {
+    let mut rng = Rng::new(seed);
+    let mut difficulty = Some(difficulty);
+    while let Some(current) = difficulty {
+        if let Some(level) = (0..MAX_SEEDS).find_map(|_| generate(rng.next_u64(), current)) {
+            return level;
+        }
+        difficulty = current.easier();
+    }
+    fallback_level()
+}
+
+/// The first level that works out for the seed, it always gives one
+pub fn seeded_level(seed: u64, difficulty: Difficulty) -> GameLevel {
+    first_generated(seed, difficulty, generate)
+}
+
+/// The level at the given depth of an endless run
+pub fn endless_level(seed: u64, depth: u32) -> GameLevel {
+    let difficulty = Difficulty::for_depth(depth);
+    let mut level = seeded_level(seed ^ ((depth as u64) << 32), difficulty);
+    level.name = format!("Endless #{}", depth + 1);
+    level
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_level() {
+        let a = serde_json::to_string(&endless_level(20201003, 4)).unwrap();
+        let b = serde_json::to_string(&endless_level(20201003, 4)).unwrap();
+        let c = serde_json::to_string(&endless_level(20201003, 5)).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn generated_levels_are_in_the_band() {
+        for &difficulty in &[Difficulty::Easy, Difficulty::Normal, Difficulty::Hard] {
+            for seed in 0..4 {
+                let level = generate(seed, difficulty).unwrap();
+                assert!(difficulty.ring_counts().contains(&level.rings.len()));
+                let score = solve(&level, &player()).unwrap().score;
+                assert!(difficulty.efficiency_band().contains(&score));
+            }
+        }
+    }
+
+    #[test]
+    fn failing_seeds_are_capped() {
+        let tries = Cell::new(Vec::new());
+        let level = first_generated(1, Difficulty::Hard, |_, difficulty| {
+            let mut list = tries.take();
+            list.push(difficulty);
+            tries.set(list);
+            None
+        });
+        let tries = tries.take();
+        assert_eq!(tries.len(), MAX_SEEDS * 3);
+        assert_eq!(tries[MAX_SEEDS], Difficulty::Normal);
+        assert_eq!(tries.last(), Some(&Difficulty::Easy));
+        assert!(solve(&level, &player()).is_some());
+
+        // the easier difficulty is used as soon as it works out
+        let level = first_generated(1, Difficulty::Hard, |seed, difficulty| {
+            if difficulty == Difficulty::Hard {
+                None
+            } else {
+                generate(seed, difficulty)
+            }
+        });
+        assert!(Difficulty::Normal
+            .ring_counts()
+            .contains(&level.rings.len()));
+    }
+
+    #[test]
+    fn rng_stays_in_range() {
+        let mut rng = Rng::new(42);
+        for _ in 0..1000 {
+            let x = rng.range(2.0..3.0);
+            assert!((2.0..3.0).contains(&x));
+            assert!(rng.index(7) < 7);
+        }
+    }
+}
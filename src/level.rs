@@ -1,8 +1,32 @@
+use std::collections::{BTreeMap, HashMap};
+
 use nalgebra::Vector2;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-use crate::states::main_game::Disruption;
+use crate::daily::DailyRecord;
 use crate::engine::util::Bitmap;
+use crate::engine::versioned::{Migration, Versioned};
+use crate::motion::Motion;
+use crate::obstacle::{Obstacle, ObstacleKind};
+use crate::pack::LevelPack;
+use crate::recording::Recording;
+use crate::shape::{self, Shape, Span};
+use crate::simulation::Disruption;
+use crate::stats::LevelStats;
+
+// width to height, the smaller one is what the ring sizes are relative to
+const ASPECT_RATIOS: &[(f64, f64)] =
+    &[(16.0, 9.0), (4.0, 3.0), (1.0, 1.0), (3.0, 4.0), (9.0, 16.0)];
+
+// used to turn relative ring sizes into pixels when comparing with widths
+const REFERENCE_SIZE: f64 = 720.0;
+
+// how many points along the path of a moving ring are checked
+const MOTION_SAMPLES: usize = 64;
+
+// in degrees, the smaller parts of a ring are too hard to see and to hit
+const MIN_DRAWN_ANGLE: f64 = 10.0;
 
 fn default_offset() -> Vector2<f64> {
     [0.0, 0.0].into()
@@ -20,9 +44,27 @@ fn default_color() -> String {
     "black".into()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where a cut has to cross a ring to disrupt it, relative to the particle on it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CutRule {
+    #[default]
+    Anywhere,
+    /// Within the half turn in front of the particle
+    Ahead,
+    Behind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnergyRing {
     pub radius: f64,
+    #[serde(default, skip_serializing_if = "Shape::is_circle")]
+    pub shape: Shape,
+    /// Only this part of the ring is there when set, the cuts elsewhere miss it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arc: Option<Span>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gaps: Vec<Span>,
 
     #[serde(default = "default_offset")]
     pub offset: Vector2<f64>,
@@ -36,16 +78,41 @@ pub struct EnergyRing {
     #[serde(default = "default_restore_time")]
     pub restore_time: f64,
 
+    /// Turns per second of the particles on it, the negative ones go the other way
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// The particles turn around when they jump onto it
+    #[serde(default)]
+    pub reverse: bool,
+    #[serde(default)]
+    pub cut_rule: CutRule,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<Motion>,
+
     #[serde(skip)]
     pub disrupted_time: f64,
 }
 
-impl Clone for EnergyRing {
-    fn clone(&self) -> Self {
+fn default_speed() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParticleStart {
+    /// The ring with the most energy when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ring: Option<usize>,
+    /// In turns per second
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+}
+
+impl Default for ParticleStart {
+    fn default() -> Self {
         Self {
-            disrupted_time: 0.0,
-            color: self.color.clone(),
-            ..*self
+            ring: None,
+            speed: default_speed(),
         }
     }
 }
@@ -55,36 +122,476 @@ pub struct GameLevel {
     pub name: String,
     pub energy: f64,
     pub rings: Vec<EnergyRing>,
+    /// The efficiency needed for one, two and three stars
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stars: Option<[f64; 3]>,
+    /// Groups of ring indices, a single cut across all of a group disrupts it at once
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obstacles: Vec<Obstacle>,
+    /// A single particle on the ring with the most energy when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub particles: Vec<ParticleStart>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A mistake in a level that the game itself would not notice
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The star thresholds of the levels that do not set their own
+pub const DEFAULT_STARS: [f64; 3] = [50.0, 75.0, 90.0];
+
+/// Filled and empty stars, out of three
+pub fn star_text(stars: u32) -> String {
+    format!(
+        "{}{}",
+        "★".repeat(stars as usize),
+        "☆".repeat(3 - stars as usize)
+    )
+}
+
+impl GameLevel {
+    pub fn new(name: String, energy: f64, rings: Vec<EnergyRing>) -> Self {
+        Self {
+            name,
+            energy,
+            rings,
+            stars: None,
+            links: Vec::new(),
+            obstacles: Vec::new(),
+            particles: Vec::new(),
+        }
+    }
+
+    /// Everything wrong with the level on its own, like rings that can not all
+    /// be paid for, that fall off the screen or that are drawn over each other
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut report = |severity, message| problems.push(Problem { severity, message });
+
+        if self.rings.is_empty() {
+            report(Severity::Error, "level has no rings".into());
+        }
+
+        let base_energy = self.rings.iter().map(|r| r.base_energy).sum::<f64>();
+        if base_energy > self.energy {
+            report(
+                Severity::Error,
+                format!(
+                    "impossible: total base energy {} exceeds the level energy {}",
+                    base_energy, self.energy
+                ),
+            );
+        } else if base_energy == self.energy {
+            report(
+                Severity::Error,
+                "no free energy left, the score is undefined".into(),
+            );
+        }
+
+        if let Some([one, two, three]) = self.stars {
+            if !(0.0 < one && one < two && two < three && three <= 100.0) {
+                report(
+                    Severity::Error,
+                    format!(
+                        "star thresholds {}, {}, {} are not increasing within 0-100%",
+                        one, two, three
+                    ),
+                );
+            }
+        }
+
+        for (o, obstacle) in self.obstacles.iter().enumerate() {
+            let problem = match obstacle.kind {
+                ObstacleKind::Shield { length, .. } if length <= 0.0 => {
+                    Some("a non-positive length")
+                }
+                ObstacleKind::Shield { period, .. } if period.unwrap_or(1.0) == 0.0 => {
+                    Some("a zero period")
+                }
+                ObstacleKind::NoCut { radius, .. } if radius <= 0.0 => {
+                    Some("a non-positive radius")
+                }
+                _ if obstacle.surcharge.unwrap_or(0.0) < 0.0 => Some("a negative surcharge"),
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                report(Severity::Error, format!("obstacle #{} has {}", o, problem));
+            }
+        }
+
+        for (p, particle) in self.particles.iter().enumerate() {
+            if let Some(ring) = particle.ring.filter(|&ring| ring >= self.rings.len()) {
+                report(
+                    Severity::Error,
+                    format!(
+                        "particle #{} starts on ring #{}, which is not there",
+                        p, ring
+                    ),
+                );
+            }
+            if particle.speed == 0.0 {
+                report(Severity::Warning, format!("particle #{} does not move", p));
+            }
+        }
+
+        let mut linked = HashMap::new();
+        for (g, group) in self.links.iter().enumerate() {
+            if group.len() < 2 {
+                report(
+                    Severity::Error,
+                    format!("link group #{} needs at least two rings", g),
+                );
+            }
+            for &ring in group {
+                if ring >= self.rings.len() {
+                    report(
+                        Severity::Error,
+                        format!("link group #{} has ring #{}, which is not there", g, ring),
+                    );
+                } else if let Some(other) = linked.insert(ring, g) {
+                    report(
+                        Severity::Error,
+                        format!(
+                            "ring #{} is in both link groups #{} and #{}",
+                            ring, other, g
+                        ),
+                    );
+                }
+            }
+        }
+
+        for (i, ring) in self.rings.iter().enumerate() {
+            if ring.radius <= 0.0 {
+                report(
+                    Severity::Error,
+                    format!("ring #{} has non-positive radius", i),
+                );
+            }
+            let bad_shape = match ring.shape {
+                Shape::Circle => false,
+                Shape::Ellipse { ratio, .. } => ratio <= 0.0,
+                Shape::Polygon { sides, .. } => sides < 3,
+                Shape::RoundedRect { ratio, corner, .. } => {
+                    ratio <= 0.0 || corner < 0.0 || corner > ratio.min(1.0)
+                }
+            };
+            if bad_shape {
+                report(
+                    Severity::Error,
+                    format!("ring #{} has an invalid {}", i, ring.shape.name()),
+                );
+            }
+            let drawn = ring
+                .drawn_spans()
+                .iter()
+                .map(|(start, end)| end - start)
+                .sum::<f64>();
+            if drawn < MIN_DRAWN_ANGLE.to_radians() {
+                report(
+                    Severity::Error,
+                    format!("ring #{} has almost nothing left between its gaps", i),
+                );
+            }
+            if ring.speed == 0.0 {
+                report(
+                    Severity::Warning,
+                    format!("the particles stand still on ring #{}", i),
+                );
+            }
+            if ring.restore_time <= 0.0 {
+                report(
+                    Severity::Error,
+                    format!("ring #{} has non-positive restore time", i),
+                );
+            }
+
+            let positions = match &ring.motion {
+                Some(motion) => {
+                    if motion.period() == 0.0 {
+                        report(
+                            Severity::Error,
+                            format!("ring #{} moves with a zero period", i),
+                        );
+                        continue;
+                    }
+                    if let Some(around) = motion.anchor() {
+                        if around == i || around >= self.rings.len() {
+                            report(
+                                Severity::Error,
+                                format!("ring #{} orbits ring #{}, which is not there", i, around),
+                            );
+                        }
+                    }
+                    let step = motion.period().abs() / MOTION_SAMPLES as f64;
+                    (0..MOTION_SAMPLES)
+                        .map(|s| self.ring_offsets(step * s as f64)[i])
+                        .collect()
+                }
+                None => vec![ring.offset],
+            };
+
+            let extent = ring.shape.extent() * ring.radius;
+            let off_screen = ASPECT_RATIOS
+                .iter()
+                .filter(|(w, h)| {
+                    let min = w.min(*h);
+                    let half_w = w / min / 2.0;
+                    let half_h = h / min / 2.0;
+                    positions.iter().any(|offset| {
+                        offset.x.abs() + extent.x > half_w || offset.y.abs() + extent.y > half_h
+                    })
+                })
+                .map(|(w, h)| format!("{}:{}", w, h))
+                .collect::<Vec<_>>();
+            if !off_screen.is_empty() {
+                report(
+                    Severity::Warning,
+                    format!("ring #{} falls off-screen at {}", i, off_screen.join(", ")),
+                );
+            }
+
+            for (j, other) in self.rings.iter().enumerate().skip(i + 1) {
+                // the moving ones only overlap for a moment, if at all,
+                // and the different shapes only touch in a few points
+                if ring.motion.is_some() || other.motion.is_some() || ring.shape != other.shape {
+                    continue;
+                }
+                let center_dist = ring.offset.metric_distance(&other.offset) * REFERENCE_SIZE;
+                let radius_dist = (ring.radius - other.radius).abs() * REFERENCE_SIZE;
+                if center_dist < 1.0 && radius_dist <= (ring.width + other.width) / 2.0 + 1.0 {
+                    report(Severity::Error, format!("rings #{} and #{} overlap", i, j));
+                }
+            }
+        }
+        problems
+    }
+
+    pub fn star_thresholds(&self) -> [f64; 3] {
+        self.stars.unwrap_or(DEFAULT_STARS)
+    }
+
+    pub fn stars_for(&self, score: f64) -> u32 {
+        self.star_thresholds()
+            .iter()
+            .filter(|&&s| score >= s)
+            .count() as u32
+    }
+
+    /// Where the centers of the rings are at the given time, same as the offsets when none move
+    pub fn ring_offsets(&self, time: f64) -> Vec<Vector2<f64>> {
+        (0..self.rings.len())
+            .map(|idx| self.ring_offset(idx, time, 0))
+            .collect()
+    }
+
+    fn ring_offset(&self, idx: usize, time: f64, depth: usize) -> Vector2<f64> {
+        let ring = &self.rings[idx];
+        let motion = match &ring.motion {
+            Some(motion) => motion,
+            None => return ring.offset,
+        };
+        // rings can orbit the orbiting ones, but not in a loop
+        let anchor = motion
+            .anchor()
+            .filter(|&around| around != idx && around < self.rings.len())
+            .filter(|_| depth < self.rings.len())
+            .map(|around| {
+                let now = self.ring_offset(around, time, depth + 1);
+                (self.rings[around].offset, now)
+            });
+        motion.offset_at(ring.offset, anchor, time)
+    }
+
+    /// Also fixes up the indices of the other rings in the links, the orbits and the particles
+    pub fn remove_ring(&mut self, ring: usize) {
+        self.rings.remove(ring);
+        for group in &mut self.links {
+            group.retain(|&idx| idx != ring);
+            for idx in group.iter_mut().filter(|idx| **idx > ring) {
+                *idx -= 1;
+            }
+        }
+        self.links.retain(|group| group.len() > 1);
+        for particle in &mut self.particles {
+            particle.ring = match particle.ring {
+                Some(idx) if idx == ring => None,
+                Some(idx) if idx > ring => Some(idx - 1),
+                other => other,
+            };
+        }
+        for other in &mut self.rings {
+            match &mut other.motion {
+                Some(Motion::Orbit { around, .. }) if *around == ring => other.motion = None,
+                Some(Motion::Orbit { around, .. }) if *around > ring => *around -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn particle_starts(&self) -> Vec<ParticleStart> {
+        if self.particles.is_empty() {
+            vec![ParticleStart::default()]
+        } else {
+            self.particles.clone()
+        }
+    }
+
+    /// The index of the link group the ring is in, if any
+    pub fn link_group(&self, ring: usize) -> Option<usize> {
+        self.links.iter().position(|group| group.contains(&ring))
+    }
+
+    pub fn has_moving_rings(&self) -> bool {
+        self.rings.iter().any(|r| r.motion.is_some())
+    }
+
+    /// Whether anything in the level changes with the time
+    pub fn is_moving(&self) -> bool {
+        // the cut rules depend on where the particles are, so on the time too
+        self.has_moving_rings()
+            || self.obstacles.iter().any(Obstacle::rotates)
+            || self.rings.iter().any(|r| r.cut_rule != CutRule::Anywhere)
+    }
+
+    /// The level as URL safe text, for putting it in a link
+    pub fn to_share_code(&self) -> String {
+        let json = serde_json::to_string(self).unwrap();
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn from_share_code(code: &str) -> Option<GameLevel> {
+        let json = base64::decode_config(code, base64::URL_SAFE_NO_PAD).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// A pack that is not the main one, its scores are kept separately
+#[derive(Debug, Clone)]
+pub struct CustomPack {
+    /// Where the pack came from, part of the keys of its scores
+    pub key: String,
+    pub pack: LevelPack,
+}
+
+impl CustomPack {
+    pub fn score_key(&self, level: &GameLevel) -> String {
+        score_key(&self.key, level)
+    }
+}
+
+pub fn score_key(pack_key: &str, level: &GameLevel) -> String {
+    format!("{}/{}", pack_key, level.name)
+}
+
+/// Accepts a single level, a list of them or a whole pack
+pub fn parse_levels(json: &str) -> serde_json::Result<Vec<GameLevel>> {
+    LevelPack::parse(json, "").map(|pack| pack.levels)
 }
 
 impl EnergyRing {
-    pub fn intersects(&self, center: Vector2<f64>, disruption: &Disruption) -> bool {
+    /// A ring with everything else as it is when left out of the levels file
+    pub fn new(offset: Vector2<f64>, radius: f64, base_energy: f64) -> Self {
+        Self {
+            radius,
+            shape: Shape::Circle,
+            arc: None,
+            gaps: Vec::new(),
+            offset,
+            width: default_width(),
+            color: default_color(),
+            base_energy,
+            restore_time: default_restore_time(),
+            speed: default_speed(),
+            reverse: false,
+            cut_rule: CutRule::Anywhere,
+            motion: None,
+            disrupted_time: 0.0,
+        }
+    }
+
+    /// The `offset` is where the ring is at the time, see `GameLevel::ring_offsets`
+    pub fn intersects(
+        &self,
+        center: Vector2<f64>,
+        offset: Vector2<f64>,
+        disruption: &Disruption,
+    ) -> bool {
+        !self.hit_angles(center, offset, disruption).is_empty()
+    }
+
+    /// The angles around the center of the ring where the cut crosses the drawn parts of it
+    pub fn hit_angles(
+        &self,
+        center: Vector2<f64>,
+        offset: Vector2<f64>,
+        disruption: &Disruption,
+    ) -> Vec<f64> {
         let min_dim = center.min() * 2.0;
-        let center = center + self.offset * min_dim;
-        let d1 = disruption.start.metric_distance(&center);
-        let d2 = disruption.end.metric_distance(&center);
+        let center = center + offset * min_dim;
         let r = min_dim * self.radius;
-        d1.min(d2) <= r && d1.max(d2) >= r
+        let spans = self.drawn_spans();
+        self.shape
+            .hits(
+                (disruption.start - center) / r,
+                (disruption.end - center) / r,
+            )
+            .iter()
+            .map(|hit| hit.y.atan2(hit.x))
+            .filter(|&angle| shape::is_drawn(&spans, angle))
+            .collect()
+    }
+
+    pub fn drawn_spans(&self) -> Vec<(f64, f64)> {
+        shape::drawn_spans(self.arc, &self.gaps)
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredData {
+    pub version: u64,
     pub passed_tutorial: bool,
     pub unlocked_level: usize,
     pub best_scores: Vec<f64>,
     pub sounds_enabled: bool,
     pub music_enabled: bool,
+    pub best_runs: Vec<Option<Recording>>,
+    /// Keyed by the level name, so that reordering the levels does not mix them up
+    pub stats: BTreeMap<String, LevelStats>,
+    /// Best scores of the levels from custom packs, keyed by `CustomPack::score_key`
+    pub custom_scores: BTreeMap<String, f64>,
+    /// Levels the player has pasted in, other custom packs are loaded from the URL
+    pub custom_levels: Vec<GameLevel>,
+    pub daily: DailyRecord,
+    /// Fastest wins in the time attack mode, in seconds, keyed like the stats
+    pub best_times: BTreeMap<String, f64>,
 }
 
 impl Default for StoredData {
     fn default() -> Self {
         Self {
+            version: Self::current_version(),
             passed_tutorial: false,
             unlocked_level: 0,
             best_scores: Vec::new(),
             sounds_enabled: true,
             music_enabled: true,
+            best_runs: Vec::new(),
+            stats: BTreeMap::new(),
+            custom_scores: BTreeMap::new(),
+            custom_levels: Vec::new(),
+            daily: DailyRecord::default(),
+            best_times: BTreeMap::new(),
         }
     }
 }
@@ -95,4 +602,524 @@ impl StoredData {
             .with_set(0, self.sounds_enabled)
             .with_set(1, self.music_enabled)
     }
+
+    pub fn level_stats(&self, level_name: &str) -> Option<&LevelStats> {
+        self.stats.get(level_name)
+    }
+
+    pub fn level_stats_mut(&mut self, level_name: &str) -> &mut LevelStats {
+        self.stats.entry(level_name.into()).or_default()
+    }
+
+    /// Keeps the time as the best one of the level when it is faster, returns whether it was
+    pub fn record_time(&mut self, key: &str, time: f64) -> bool {
+        let faster = match self.best_times.get(key) {
+            Some(&best) => time < best,
+            None => true,
+        };
+        if faster {
+            self.best_times.insert(key.into(), time);
+        }
+        faster
+    }
+}
+
+// the unversioned data had no defaults, and some of the
+// fields were added over time, so any of them might be missing
+fn migrate_v0(data: &mut Value) -> Result<(), String> {
+    let data = data.as_object_mut().ok_or("not an object")?;
+    let defaults = json!({
+        "passed_tutorial": false,
+        "unlocked_level": 0,
+        "best_scores": [],
+        "sounds_enabled": true,
+        "music_enabled": true,
+        "best_runs": [],
+    });
+    for (key, value) in defaults.as_object().unwrap() {
+        data.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    Ok(())
+}
+
+fn migrate_v1(data: &mut Value) -> Result<(), String> {
+    let data = data.as_object_mut().ok_or("not an object")?;
+    data.entry("stats").or_insert_with(|| json!({}));
+    Ok(())
+}
+
+fn migrate_v2(data: &mut Value) -> Result<(), String> {
+    let data = data.as_object_mut().ok_or("not an object")?;
+    data.entry("custom_scores").or_insert_with(|| json!({}));
+    data.entry("custom_levels").or_insert_with(|| json!([]));
+    Ok(())
+}
+
+fn migrate_v3(data: &mut Value) -> Result<(), String> {
+    let data = data.as_object_mut().ok_or("not an object")?;
+    data.entry("daily").or_insert_with(|| json!({}));
+    Ok(())
+}
+
+fn migrate_v4(data: &mut Value) -> Result<(), String> {
+    let data = data.as_object_mut().ok_or("not an object")?;
+    data.entry("best_times").or_insert_with(|| json!({}));
+    Ok(())
+}
+
+impl Versioned for StoredData {
+    const MIGRATIONS: &'static [Migration] =
+        &[migrate_v0, migrate_v1, migrate_v2, migrate_v3, migrate_v4];
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::engine::versioned::{self, LoadError};
+    use crate::simulation::DisruptionCause;
+    use crate::testing::level;
+
+    use super::*;
+
+    fn messages(level: &GameLevel, severity: Severity) -> Vec<String> {
+        level
+            .problems()
+            .into_iter()
+            .filter(|p| p.severity == severity)
+            .map(|p| p.message)
+            .collect()
+    }
+
+    #[test]
+    fn good_levels_have_no_problems() {
+        assert_eq!(level(100.0, &[(0.1, 10.0), (0.2, 5.0)]).problems(), vec![]);
+    }
+
+    #[test]
+    fn energy_has_to_cover_the_rings() {
+        let level = level(15.0, &[(0.1, 10.0), (0.2, 5.0)]);
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["no free energy left, the score is undefined"]
+        );
+        let mut level = level;
+        level.energy = 12.0;
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["impossible: total base energy 15 exceeds the level energy 12"]
+        );
+    }
+
+    #[test]
+    fn rings_have_to_fit_and_stay_apart() {
+        let mut level = level(100.0, &[(0.1, 10.0), (0.1, 5.0), (0.3, 1.0)]);
+        level.rings[2].offset.x = 0.4;
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["rings #0 and #1 overlap"]
+        );
+        // only the wide screens have the room for it
+        assert_eq!(
+            messages(&level, Severity::Warning),
+            vec!["ring #2 falls off-screen at 4:3, 1:1, 3:4, 9:16"]
+        );
+    }
+
+    #[test]
+    fn moving_rings_are_checked_along_their_path() {
+        let level: GameLevel = serde_json::from_str(
+            r#"{
+                "name": "a",
+                "energy": 10,
+                "rings": [
+                    { "radius": 0.1, "base_energy": 1,
+                      "motion": { "type": "ping-pong", "to": [0.45, 0], "period": 2 } },
+                    { "radius": 0.1, "base_energy": 2 },
+                    { "radius": 0.2, "base_energy": 3,
+                      "motion": { "type": "orbit", "around": 5, "period": 1 } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        // the first two only overlap at the start of the path
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["ring #2 orbits ring #5, which is not there"]
+        );
+        assert_eq!(
+            messages(&level, Severity::Warning),
+            vec!["ring #0 falls off-screen at 1:1, 3:4, 9:16"]
+        );
+    }
+
+    #[test]
+    fn shapes_have_to_be_valid() {
+        let mut level = level(100.0, &[(0.3, 10.0), (0.3, 5.0), (0.1, 1.0)]);
+        level.rings[0].shape = Shape::Ellipse {
+            ratio: 0.5,
+            rotation: 0.0,
+        };
+        level.rings[2].shape = Shape::Polygon {
+            sides: 2,
+            rotation: 0.0,
+        };
+        // the circle and the ellipse of the same radius only touch
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["ring #2 has an invalid polygon"]
+        );
+    }
+
+    #[test]
+    fn rings_have_to_be_mostly_drawn() {
+        let level: GameLevel = serde_json::from_str(
+            r#"{
+                "name": "a",
+                "energy": 10,
+                "rings": [
+                    { "radius": 0.1, "base_energy": 1,
+                      "arc": { "start_angle": 0, "end_angle": 90 } },
+                    { "radius": 0.2, "base_energy": 2,
+                      "arc": { "start_angle": 0, "end_angle": 90 },
+                      "gaps": [{ "start_angle": 5, "end_angle": 88 }] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["ring #1 has almost nothing left between its gaps"]
+        );
+    }
+
+    #[test]
+    fn particles_have_to_start_on_a_ring() {
+        let mut level = level(100.0, &[(0.1, 10.0), (0.2, 5.0)]);
+        level.particles =
+            serde_json::from_value(json!([{ "ring": 1 }, { "ring": 2, "speed": 0 }])).unwrap();
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["particle #1 starts on ring #2, which is not there"]
+        );
+        assert_eq!(
+            messages(&level, Severity::Warning),
+            vec!["particle #1 does not move"]
+        );
+    }
+
+    #[test]
+    fn rings_should_move_the_particles() {
+        let mut level = level(100.0, &[(0.1, 10.0), (0.2, 5.0)]);
+        level.rings[1].speed = 0.0;
+        assert_eq!(
+            messages(&level, Severity::Warning),
+            vec!["the particles stand still on ring #1"]
+        );
+    }
+
+    #[test]
+    fn links_have_to_be_valid() {
+        let mut level = level(100.0, &[(0.1, 10.0), (0.2, 5.0), (0.3, 1.0)]);
+        level.links = vec![vec![0, 1], vec![2], vec![1, 3]];
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec![
+                "link group #1 needs at least two rings",
+                "ring #1 is in both link groups #0 and #2",
+                "link group #2 has ring #3, which is not there",
+            ]
+        );
+    }
+
+    #[test]
+    fn obstacles_have_to_be_valid() {
+        let mut level = level(100.0, &[(0.1, 10.0)]);
+        level.obstacles = serde_json::from_str(
+            r#"[
+                { "type": "shield", "center": [0, 0], "length": 0.2, "period": 0 },
+                { "type": "no-cut", "center": [0.3, 0], "radius": 0.1, "surcharge": -1 },
+                { "type": "no-cut", "center": [0.3, 0], "radius": 0.1, "surcharge": 5 }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec![
+                "obstacle #0 has a zero period",
+                "obstacle #1 has a negative surcharge",
+            ]
+        );
+    }
+
+    #[test]
+    fn star_thresholds_have_to_increase() {
+        let mut level = level(100.0, &[(0.1, 10.0)]);
+        level.stars = Some([60.0, 95.0, 80.0]);
+        assert_eq!(
+            messages(&level, Severity::Error),
+            vec!["star thresholds 60, 95, 80 are not increasing within 0-100%"]
+        );
+        level.stars = Some([60.0, 80.0, 95.0]);
+        assert!(level.problems().is_empty());
+    }
+
+    #[test]
+    fn unversioned_data_is_preserved() {
+        let data: StoredData = versioned::load(
+            r#"{
+                "passed_tutorial": true,
+                "unlocked_level": 3,
+                "best_scores": [90.5, 42.0],
+                "sounds_enabled": false,
+                "music_enabled": true
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(data.version, 5);
+        assert!(data.passed_tutorial);
+        assert_eq!(data.unlocked_level, 3);
+        assert_eq!(data.best_scores, vec![90.5, 42.0]);
+        assert!(!data.sounds_enabled);
+        assert!(data.music_enabled);
+        assert!(data.best_runs.is_empty());
+        assert!(data.stats.is_empty());
+    }
+
+    #[test]
+    fn v1_gets_empty_stats() {
+        let data: StoredData = versioned::load(
+            r#"{
+                "version": 1,
+                "passed_tutorial": true,
+                "unlocked_level": 1,
+                "best_scores": [50.0],
+                "sounds_enabled": true,
+                "music_enabled": true,
+                "best_runs": [null]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(data.version, 5);
+        assert_eq!(data.best_scores, vec![50.0]);
+        assert!(data.stats.is_empty());
+        assert!(data.custom_scores.is_empty());
+        assert!(data.daily.history.is_empty());
+    }
+
+    #[test]
+    fn v0_missing_fields_get_defaults() {
+        let mut data = json!({ "unlocked_level": 2 });
+        migrate_v0(&mut data).unwrap();
+
+        assert_eq!(
+            data,
+            json!({
+                "passed_tutorial": false,
+                "unlocked_level": 2,
+                "best_scores": [],
+                "sounds_enabled": true,
+                "music_enabled": true,
+                "best_runs": [],
+            })
+        );
+    }
+
+    #[test]
+    fn v1_gets_stats() {
+        let mut data = json!({ "unlocked_level": 2, "best_runs": [null] });
+        migrate_v1(&mut data).unwrap();
+        assert_eq!(
+            data,
+            json!({ "unlocked_level": 2, "best_runs": [null], "stats": {} })
+        );
+
+        // already there, so it is kept
+        let stats = json!({ "a": { "attempts": 3 } });
+        let mut data = json!({ "stats": stats.clone() });
+        migrate_v1(&mut data).unwrap();
+        assert_eq!(data["stats"], stats);
+    }
+
+    #[test]
+    fn v2_gets_custom_scores_and_levels() {
+        let mut data = json!({ "stats": {} });
+        migrate_v2(&mut data).unwrap();
+        assert_eq!(
+            data,
+            json!({ "stats": {}, "custom_scores": {}, "custom_levels": [] })
+        );
+    }
+
+    #[test]
+    fn v3_gets_a_daily_record() {
+        let mut data = json!({ "custom_levels": [] });
+        migrate_v3(&mut data).unwrap();
+        assert_eq!(data, json!({ "custom_levels": [], "daily": {} }));
+
+        let data: StoredData = versioned::load(
+            r#"{
+                "version": 3,
+                "passed_tutorial": true,
+                "unlocked_level": 2,
+                "best_scores": [50.0, 60.0],
+                "sounds_enabled": true,
+                "music_enabled": false,
+                "best_runs": [null, null],
+                "stats": {},
+                "custom_scores": {},
+                "custom_levels": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data.unlocked_level, 2);
+        assert_eq!(data.daily, DailyRecord::default());
+    }
+
+    #[test]
+    fn v4_gets_best_times() {
+        let mut data = json!({ "version": 4, "daily": {} });
+        migrate_v4(&mut data).unwrap();
+        assert_eq!(data, json!({ "version": 4, "daily": {}, "best_times": {} }));
+
+        let data: StoredData = versioned::load(
+            r#"{
+                "version": 4,
+                "passed_tutorial": true,
+                "unlocked_level": 2,
+                "best_scores": [50.0, 60.0],
+                "sounds_enabled": true,
+                "music_enabled": false,
+                "best_runs": [null, null],
+                "stats": {},
+                "custom_scores": {},
+                "custom_levels": [],
+                "daily": { "history": { "2020-02-02": 90.0 }, "streak": 1, "best_streak": 1 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data.version, 5);
+        assert_eq!(data.daily.history.get("2020-02-02"), Some(&Some(90.0)));
+        assert!(data.best_times.is_empty());
+    }
+
+    #[test]
+    fn only_faster_times_are_kept() {
+        let mut data = StoredData::default();
+        assert!(data.record_time("a", 12.5));
+        assert!(data.record_time("a", 10.0));
+        assert!(!data.record_time("a", 11.0));
+        assert!(!data.record_time("a", 10.0));
+        assert!(data.record_time("b", 20.0));
+        assert_eq!(data.best_times.get("a"), Some(&10.0));
+        assert_eq!(data.best_times.get("b"), Some(&20.0));
+    }
+
+    #[test]
+    fn current_version_roundtrips() {
+        let data = StoredData {
+            unlocked_level: 4,
+            ..Default::default()
+        };
+        let loaded: StoredData = versioned::load(&serde_json::to_string(&data).unwrap()).unwrap();
+
+        assert_eq!(loaded.version, StoredData::current_version());
+        assert_eq!(loaded.unlocked_level, 4);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let result = versioned::load::<StoredData>(r#"{ "version": 999 }"#);
+        assert!(matches!(result, Err(LoadError::TooNew(999))));
+    }
+
+    #[test]
+    fn single_and_multiple_levels_are_parsed() {
+        let level =
+            r#"{ "name": "a", "energy": 10, "rings": [{ "radius": 0.1, "base_energy": 1 }] }"#;
+        assert_eq!(parse_levels(level).unwrap().len(), 1);
+        assert_eq!(
+            parse_levels(&format!("[{0}, {0}]", level)).unwrap().len(),
+            2
+        );
+        assert!(parse_levels(r#"{ "name": "a" }"#).is_err());
+    }
+
+    #[test]
+    fn stars_use_the_level_thresholds() {
+        let mut level: GameLevel =
+            serde_json::from_str(r#"{ "name": "a", "energy": 10, "rings": [] }"#).unwrap();
+        assert_eq!(level.stars_for(80.0), 2);
+
+        level.stars = Some([60.0, 80.0, 95.0]);
+        assert_eq!(level.stars_for(80.0), 2);
+        assert_eq!(level.stars_for(79.9), 1);
+        assert_eq!(level.stars_for(95.0), 3);
+        assert_eq!(star_text(1), "★☆☆");
+    }
+
+    #[test]
+    fn rings_orbit_moving_rings() {
+        let level: GameLevel = serde_json::from_str(
+            r#"{
+                "name": "a",
+                "energy": 10,
+                "rings": [
+                    { "radius": 0.1, "base_energy": 1,
+                      "motion": { "type": "ping-pong", "to": [0.2, 0], "period": 2 } },
+                    { "radius": 0.1, "base_energy": 1, "offset": [0, 0.1],
+                      "motion": { "type": "orbit", "around": 0, "period": 4 } },
+                    { "radius": 0.1, "base_energy": 1, "offset": [0.3, 0],
+                      "motion": { "type": "orbit", "around": 3, "period": 1 } },
+                    { "radius": 0.1, "base_energy": 1,
+                      "motion": { "type": "orbit", "around": 2, "period": 1 } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let offsets = level.ring_offsets(1.0);
+        assert!((offsets[0] - Vector2::new(0.2, 0.0)).norm() < 1e-9);
+        // a quarter turn from straight above the first ring, which has moved by then
+        assert!((offsets[1] - Vector2::new(0.1, 0.0)).norm() < 1e-9);
+        // the last two orbit each other, which just has to not loop forever
+        assert!(offsets[2].x.is_finite() && offsets[3].x.is_finite());
+    }
+
+    #[test]
+    fn cuts_only_hit_the_drawn_arc() {
+        let ring: EnergyRing = serde_json::from_str(
+            r#"{ "radius": 0.25, "base_energy": 1,
+                 "arc": { "start_angle": 0, "end_angle": 180 },
+                 "gaps": [{ "start_angle": 80, "end_angle": 100 }] }"#,
+        )
+        .unwrap();
+        let center = Vector2::new(100.0, 100.0);
+        let cut = |start: [f64; 2], end: [f64; 2]| Disruption {
+            start: start.into(),
+            end: end.into(),
+            start_time: 0.0,
+            cause: DisruptionCause::Mouse,
+        };
+        let offset = Vector2::zeros();
+        assert!(ring.intersects(center, offset, &cut([130.0, 130.0], [140.0, 140.0])));
+        // the top half is not there, and neither is the bottom under the gap
+        assert!(!ring.intersects(center, offset, &cut([100.0, 40.0], [100.0, 60.0])));
+        assert!(!ring.intersects(center, offset, &cut([100.0, 140.0], [100.0, 160.0])));
+        // goes through both halves, so it still hits the bottom one
+        assert!(ring.intersects(center, offset, &cut([60.0, 40.0], [140.0, 160.0])));
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(matches!(
+            versioned::load::<StoredData>("[1, 2, 3]"),
+            Err(LoadError::NotAnObject)
+        ));
+        assert!(matches!(
+            versioned::load::<StoredData>("{ not json"),
+            Err(LoadError::Json(_))
+        ));
+    }
 }
@@ -4,12 +4,28 @@ use web_sys::Response;
 
 use crate::engine::util::{Mut, Bitmap};
 use engine::{sound::Sound, util::setup_panic_hook, window, Game, GameRun, GameState, Resources};
-use level::{GameLevel, StoredData};
+use level::{CustomPack, GameLevel, StoredData};
+use pack::{LevelPack, UnlockRule};
 use states::main_menu::MainMenuState;
 
+pub mod daily;
 mod engine;
-mod level;
+pub mod generator;
+pub mod level;
+pub mod motion;
+pub mod obstacle;
+pub mod pack;
+pub mod recording;
+pub mod save_code;
+pub mod shape;
+pub mod simulation;
+pub mod solver;
 mod states;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+
+const LEVELS_FILE: &str = "assets/levels.json";
 
 #[derive(Debug)]
 pub struct Sounds {
@@ -45,7 +61,8 @@ impl Sounds {
 #[derive(Debug)]
 pub struct QuantumLoops {
     sounds: Sounds,
-    levels: Mut<Option<Vec<GameLevel>>>,
+    main_pack: Mut<Option<LevelPack>>,
+    custom_packs: Mut<Vec<CustomPack>>,
 }
 
 impl QuantumLoops {
@@ -54,36 +71,118 @@ impl QuantumLoops {
     }
 
     pub fn get_level(&self, level: usize) -> Option<GameLevel> {
-        self.levels
+        self.main_pack
             .borrow_mut()
             .as_ref()
-            .and_then(|levels| levels.get(level).cloned())
+            .and_then(|pack| pack.levels.get(level).cloned())
     }
 
     pub fn level_count(&self) -> usize {
-        self.levels
+        self.main_pack
+            .borrow()
+            .as_ref()
+            .map(|pack| pack.levels.len())
+            .unwrap_or_default()
+    }
+
+    /// The levels from the levels file, empty until they are loaded
+    pub fn levels(&self) -> Vec<GameLevel> {
+        self.main_pack
             .borrow()
             .as_ref()
-            .map(Vec::len)
+            .map(|pack| pack.levels.clone())
             .unwrap_or_default()
     }
 
+    pub fn main_pack(&self) -> Option<LevelPack> {
+        self.main_pack.borrow().clone()
+    }
+
+    /// The packs from the URL, the pasted levels are in the storage
+    pub fn custom_packs(&self) -> Vec<CustomPack> {
+        self.custom_packs.borrow().clone()
+    }
+
     fn load_levels(&self) {
-        let moved_levels = self.levels.clone();
+        let moved_pack = self.main_pack.clone();
+        spawn_local(async move {
+            let text = async move {
+                let response: Response =
+                    JsFuture::from(window().fetch_with_str(LEVELS_FILE))
+                        .await?
+                        .dyn_into()?;
+                JsFuture::from(response.text()?).await
+            }
+            .await;
+            let pack = match text.map(|text| text.as_string().unwrap_or_default()) {
+                Ok(text) => LevelPack::parse(&text, "Quantum Loops")
+                    .map_err(|e| pack::error_message(LEVELS_FILE, &e)),
+                Err(e) => Err(format!("{}: {:?}", LEVELS_FILE, e)),
+            };
+            match pack {
+                Ok(pack) => *moved_pack.borrow_mut() = Some(pack),
+                Err(e) => log::error!("Failed to load the levels: {}", e),
+            }
+        });
+    }
+
+    // ?pack=<url of a levels file>, can be repeated
+    // #level=<GameLevel::to_share_code>
+    fn load_custom_levels(&self) {
+        let location = window().location();
+
+        let search = location.search().unwrap_or_default();
+        let packs = search
+            .trim_start_matches('?')
+            .split('&')
+            .filter_map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                match (pair.next(), pair.next()) {
+                    (Some("pack"), Some(url)) => js_sys::decode_uri_component(url).ok(),
+                    _ => None,
+                }
+            })
+            .map(String::from);
+        for url in packs {
+            self.load_pack(url);
+        }
+
+        let hash = location.hash().unwrap_or_default();
+        if let Some(code) = hash.strip_prefix("#level=") {
+            match GameLevel::from_share_code(code) {
+                Some(level) => self.custom_packs.borrow_mut().push(CustomPack {
+                    key: "shared".into(),
+                    pack: LevelPack {
+                        unlock: UnlockRule::AllOpen,
+                        ..LevelPack::new("Shared level".into(), vec![level])
+                    },
+                }),
+                None => log::error!("Invalid level in the link"),
+            }
+        }
+    }
+
+    fn load_pack(&self, url: String) {
+        let moved_packs = self.custom_packs.clone();
         spawn_local(async move {
-            *moved_levels.borrow_mut() = Some(
-                async move {
-                    let response: Response =
-                        JsFuture::from(window().fetch_with_str("assets/levels.json"))
-                            .await?
-                            .dyn_into()?;
-                    JsFuture::from(response.json()?).await
+            let text = async {
+                let response: Response = JsFuture::from(window().fetch_with_str(&url))
+                    .await?
+                    .dyn_into()?;
+                JsFuture::from(response.text()?).await
+            }
+            .await;
+            let file_name = url.rsplit('/').next().unwrap_or(&url).to_owned();
+            let pack = match text.map(|text| text.as_string().unwrap_or_default()) {
+                Ok(text) => {
+                    LevelPack::parse(&text, &file_name).map_err(|e| pack::error_message(&url, &e))
                 }
-                .await
-                .unwrap()
-                .into_serde()
-                .unwrap(),
-            );
+                Err(e) => Err(format!("{}: {:?}", url, e)),
+            };
+            match pack {
+                Ok(pack) => moved_packs.borrow_mut().push(CustomPack { key: url, pack }),
+                Err(e) => log::error!("Failed to load a level pack: {}", e),
+            }
         });
     }
 }
@@ -91,13 +190,16 @@ impl QuantumLoops {
 impl Game for QuantumLoops {
     type Storage = StoredData;
 
+    const NAMESPACE: &'static str = "quantum-loops";
+
     fn load(resources: Resources) -> (Self, Box<dyn GameState<QuantumLoops>>) {
-        let levels = Default::default();
         let global = QuantumLoops {
             sounds: Sounds::load(resources),
-            levels,
+            main_pack: Default::default(),
+            custom_packs: Default::default(),
         };
         global.load_levels();
+        global.load_custom_levels();
         (global, Box::new(MainMenuState::new()))
     }
 }
@@ -0,0 +1,136 @@
+use std::f64::consts::TAU;
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+/// How the center of a ring moves, starting from its offset,
+/// all of the periods are in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Motion {
+    /// Slides to `to` and back
+    PingPong { to: Vector2<f64>, period: f64 },
+    /// Circles around the center of the ring at the index `around`,
+    /// keeping the distance it starts at, a negative period goes the other way
+    Orbit { around: usize, period: f64 },
+    /// A closed curve through the offset and then all the `points`
+    Spline {
+        points: Vec<Vector2<f64>>,
+        period: f64,
+    },
+}
+
+// a Catmull-Rom segment from `p1` to `p2`
+fn catmull_rom(
+    p0: Vector2<f64>,
+    p1: Vector2<f64>,
+    p2: Vector2<f64>,
+    p3: Vector2<f64>,
+    t: f64,
+) -> Vector2<f64> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+impl Motion {
+    /// The ring at `around` for orbits, if any
+    pub fn anchor(&self) -> Option<usize> {
+        match self {
+            Motion::Orbit { around, .. } => Some(*around),
+            _ => None,
+        }
+    }
+
+    pub fn period(&self) -> f64 {
+        match self {
+            Motion::PingPong { period, .. }
+            | Motion::Orbit { period, .. }
+            | Motion::Spline { period, .. } => *period,
+        }
+    }
+
+    /// `anchor` is where the orbited ring starts and where it is at the time
+    pub fn offset_at(
+        &self,
+        start: Vector2<f64>,
+        anchor: Option<(Vector2<f64>, Vector2<f64>)>,
+        time: f64,
+    ) -> Vector2<f64> {
+        let phase = time / self.period();
+        match self {
+            Motion::PingPong { to, .. } => {
+                let t = (1.0 - (TAU * phase).cos()) / 2.0;
+                start + (to - start) * t
+            }
+            Motion::Orbit { .. } => match anchor {
+                Some((anchor_start, anchor_now)) => {
+                    let arm = start - anchor_start;
+                    let angle = arm.y.atan2(arm.x) + TAU * phase;
+                    anchor_now + Vector2::new(angle.cos(), angle.sin()) * arm.norm()
+                }
+                None => start,
+            },
+            Motion::Spline { points, .. } => {
+                let mut path = vec![start];
+                path.extend(points.iter().copied());
+                let len = path.len();
+                let at = phase.rem_euclid(1.0) * len as f64;
+                let idx = at.floor() as usize % len;
+                let point = |i: usize| path[(idx + len + i - 1) % len];
+                catmull_rom(point(0), point(1), point(2), point(3), at.fract())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector2<f64>, b: Vector2<f64>) -> bool {
+        a.metric_distance(&b) < 1e-9
+    }
+
+    #[test]
+    fn ping_pong_goes_there_and_back() {
+        let motion = Motion::PingPong {
+            to: [0.2, 0.0].into(),
+            period: 4.0,
+        };
+        let start = Vector2::new(0.0, 0.0);
+        assert!(close(motion.offset_at(start, None, 0.0), start));
+        assert!(close(motion.offset_at(start, None, 2.0), [0.2, 0.0].into()));
+        assert!(close(motion.offset_at(start, None, 4.0), start));
+    }
+
+    #[test]
+    fn orbit_keeps_the_distance() {
+        let motion = Motion::Orbit {
+            around: 0,
+            period: 2.0,
+        };
+        let anchor_start = Vector2::new(0.0, 0.0);
+        let anchor_now = Vector2::new(0.1, 0.0);
+        let start = Vector2::new(0.1, 0.0);
+        let pos = motion.offset_at(start, Some((anchor_start, anchor_now)), 0.5);
+        assert!(close(pos, [0.1, 0.1].into()));
+    }
+
+    #[test]
+    fn spline_passes_through_the_points() {
+        let motion = Motion::Spline {
+            points: vec![[0.1, 0.0].into(), [0.1, 0.1].into()],
+            period: 3.0,
+        };
+        let start = Vector2::new(0.0, 0.0);
+        assert!(close(motion.offset_at(start, None, 0.0), start));
+        assert!(close(motion.offset_at(start, None, 1.0), [0.1, 0.0].into()));
+        assert!(close(motion.offset_at(start, None, 2.0), [0.1, 0.1].into()));
+        assert!(close(motion.offset_at(start, None, 3.0), start));
+    }
+}
@@ -0,0 +1,139 @@
+use std::f64::consts::TAU;
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::{shape::segment_hit, simulation::Disruption};
+
+/// The positions and sizes are relative like the ones of the rings,
+/// all the angles are in degrees and the periods in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ObstacleKind {
+    /// A wall centered on `center`, turning around it once a `period` when that is set,
+    /// a negative period turns the other way
+    Shield {
+        center: Vector2<f64>,
+        length: f64,
+        #[serde(default)]
+        angle: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        period: Option<f64>,
+    },
+    /// A round area where cuts are not allowed
+    NoCut { center: Vector2<f64>, radius: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    #[serde(flatten)]
+    pub kind: ObstacleKind,
+    /// The energy a cut across it costs on top, the cut is blocked when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub surcharge: Option<f64>,
+}
+
+fn distance_to_segment(p: Vector2<f64>, a: Vector2<f64>, b: Vector2<f64>) -> f64 {
+    let ab = b - a;
+    let len = ab.norm_squared();
+    let t = if len == 0.0 {
+        0.0
+    } else {
+        ((p - a).dot(&ab) / len).clamp(0.0, 1.0)
+    };
+    p.metric_distance(&(a + ab * t))
+}
+
+impl Obstacle {
+    pub fn rotates(&self) -> bool {
+        matches!(
+            self.kind,
+            ObstacleKind::Shield {
+                period: Some(_),
+                ..
+            }
+        )
+    }
+
+    pub fn blocks(&self) -> bool {
+        self.surcharge.is_none()
+    }
+
+    /// The ends of a shield at the time, `None` for the other obstacles
+    pub fn shield_at(&self, time: f64) -> Option<(Vector2<f64>, Vector2<f64>)> {
+        match self.kind {
+            ObstacleKind::Shield {
+                center,
+                length,
+                angle,
+                period,
+            } => {
+                let turned = period.map_or(0.0, |period| TAU * time / period);
+                let angle = angle.to_radians() + turned;
+                let half = Vector2::new(angle.cos(), angle.sin()) * length / 2.0;
+                Some((center - half, center + half))
+            }
+            ObstacleKind::NoCut { .. } => None,
+        }
+    }
+
+    /// Like `EnergyRing::intersects`, `center` is the center of the screen
+    pub fn crossed_by(&self, center: Vector2<f64>, time: f64, disruption: &Disruption) -> bool {
+        let min_dim = center.min() * 2.0;
+        let to_screen = |p: Vector2<f64>| center + p * min_dim;
+        match self.kind {
+            ObstacleKind::Shield { .. } => {
+                let (a, b) = self.shield_at(time).expect("a shield");
+                segment_hit(disruption.start, disruption.end, to_screen(a), to_screen(b)).is_some()
+            }
+            ObstacleKind::NoCut {
+                center: zone,
+                radius,
+            } => {
+                let dist = distance_to_segment(to_screen(zone), disruption.start, disruption.end);
+                dist <= radius * min_dim
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::DisruptionCause;
+
+    fn cut(start: [f64; 2], end: [f64; 2]) -> Disruption {
+        Disruption {
+            start: start.into(),
+            end: end.into(),
+            start_time: 0.0,
+            cause: DisruptionCause::Mouse,
+        }
+    }
+
+    #[test]
+    fn rotating_shield() {
+        let shield: Obstacle = serde_json::from_str(
+            r#"{ "type": "shield", "center": [0, 0], "length": 0.2, "period": 4 }"#,
+        )
+        .unwrap();
+        assert!(shield.blocks() && shield.rotates());
+        let center = Vector2::new(100.0, 100.0);
+        // lying flat at first, then standing up a quarter turn later
+        let vertical = cut([90.0, 110.0], [110.0, 110.0]);
+        assert!(!shield.crossed_by(center, 0.0, &vertical));
+        assert!(shield.crossed_by(center, 1.0, &vertical));
+    }
+
+    #[test]
+    fn no_cut_zone() {
+        let zone: Obstacle = serde_json::from_str(
+            r#"{ "type": "no-cut", "center": [0.1, 0], "radius": 0.05, "surcharge": 20 }"#,
+        )
+        .unwrap();
+        assert_eq!(zone.surcharge, Some(20.0));
+        let center = Vector2::new(100.0, 100.0);
+        assert!(zone.crossed_by(center, 0.0, &cut([120.0, 50.0], [120.0, 150.0])));
+        assert!(!zone.crossed_by(center, 0.0, &cut([140.0, 50.0], [140.0, 150.0])));
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+
+use crate::level::GameLevel;
+
+/// Which levels of a pack can be played
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum UnlockRule {
+    /// Every level unlocks the next one when won
+    #[default]
+    Linear,
+    AllOpen,
+    /// The level at index `i` needs `required[i]` stars from the whole pack,
+    /// the levels past the end of the list need none
+    Stars {
+        required: Vec<u32>,
+    },
+}
+
+impl UnlockRule {
+    /// Takes the best score of every level of the pack, `None` if it was never won
+    pub fn unlocked(&self, levels: &[GameLevel], scores: &[Option<f64>]) -> Vec<bool> {
+        let won = |idx: usize| scores.get(idx).copied().flatten().is_some();
+        match self {
+            UnlockRule::Linear => (0..levels.len())
+                .map(|idx| idx == 0 || won(idx - 1))
+                .collect(),
+            UnlockRule::AllOpen => vec![true; levels.len()],
+            UnlockRule::Stars { required } => {
+                let stars = total_stars(levels, scores);
+                (0..levels.len())
+                    .map(|idx| required.get(idx).copied().unwrap_or_default() <= stars)
+                    .collect()
+            }
+        }
+    }
+}
+
+pub fn total_stars(levels: &[GameLevel], scores: &[Option<f64>]) -> u32 {
+    levels
+        .iter()
+        .zip(scores)
+        .filter_map(|(level, score)| score.map(|score| level.stars_for(score)))
+        .sum()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelPack {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub unlock: UnlockRule,
+    /// Stars from all the packs together needed to open this one
+    #[serde(default)]
+    pub required_stars: u32,
+    pub levels: Vec<GameLevel>,
+}
+
+impl LevelPack {
+    pub fn new(title: String, levels: Vec<GameLevel>) -> Self {
+        Self {
+            title,
+            author: None,
+            version: None,
+            description: None,
+            unlock: UnlockRule::default(),
+            required_stars: 0,
+            levels,
+        }
+    }
+
+    /// Accepts a pack, a bare list of levels or a single level,
+    /// the `title` is used when there is none in the file
+    pub fn parse(json: &str, title: &str) -> serde_json::Result<Self> {
+        // the format is picked before parsing it for real, an untagged enum
+        // would only say that none of them matched, without where and why
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        Ok(if value.is_array() {
+            // the format from before there were packs
+            Self::new(title.into(), serde_json::from_str(json)?)
+        } else if value.get("levels").is_some() {
+            serde_json::from_str(json)?
+        } else {
+            Self::new(title.into(), vec![serde_json::from_str(json)?])
+        })
+    }
+}
+
+/// The error as `<file>:<line>:<column>: <message>`, like the compilers report them
+pub fn error_message(file: &str, error: &serde_json::Error) -> String {
+    // serde_json appends the position to the message itself
+    let message = error.to_string();
+    let message = message.split(" at line ").next().unwrap_or_default();
+    format!("{}:{}:{}: {}", file, error.line(), error.column(), message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str =
+        r#"{ "name": "a", "energy": 10, "rings": [{ "radius": 0.1, "base_energy": 1 }] }"#;
+
+    #[test]
+    fn bare_arrays_are_still_accepted() {
+        let pack = LevelPack::parse(&format!("[{0}, {0}]", LEVEL), "Default").unwrap();
+        assert_eq!(pack.title, "Default");
+        assert_eq!(pack.levels.len(), 2);
+        assert_eq!(pack.unlock, UnlockRule::Linear);
+    }
+
+    #[test]
+    fn pack_metadata_is_parsed() {
+        let pack = LevelPack::parse(
+            &format!(
+                r#"{{
+                    "title": "Hard ones",
+                    "author": "someone",
+                    "version": "1.2",
+                    "unlock": {{ "type": "stars", "required": [0, 2] }},
+                    "required_stars": 5,
+                    "levels": [{0}, {0}]
+                }}"#,
+                LEVEL
+            ),
+            "Default",
+        )
+        .unwrap();
+        assert_eq!(pack.title, "Hard ones");
+        assert_eq!(pack.author.as_deref(), Some("someone"));
+        assert_eq!(pack.description, None);
+        assert_eq!(pack.required_stars, 5);
+        assert_eq!(
+            pack.unlock,
+            UnlockRule::Stars {
+                required: vec![0, 2]
+            }
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_position() {
+        let error = LevelPack::parse("[\n  { \"name\": 1 }\n]", "").unwrap_err();
+        let message = error_message("pack.json", &error);
+        assert!(message.starts_with("pack.json:2:13: "), "{}", message);
+        assert!(!message.contains(" at line "));
+    }
+
+    #[test]
+    fn unlock_rules() {
+        let pack = LevelPack::parse(&format!("[{0}, {0}, {0}]", LEVEL), "").unwrap();
+        let scores = [Some(100.0), None, None];
+
+        assert_eq!(
+            UnlockRule::Linear.unlocked(&pack.levels, &scores),
+            vec![true, true, false]
+        );
+        assert_eq!(
+            UnlockRule::AllOpen.unlocked(&pack.levels, &scores),
+            vec![true; 3]
+        );
+        let stars = UnlockRule::Stars {
+            required: vec![0, 3, 4],
+        };
+        assert_eq!(
+            stars.unlocked(&pack.levels, &scores),
+            vec![true, true, false]
+        );
+    }
+}
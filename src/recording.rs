@@ -0,0 +1,277 @@
+use nalgebra::Vector2;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    engine::event::Event,
+    level::GameLevel,
+    simulation::{Input, Simulation, Status},
+};
+
+// how long the JSON of a stored run can get, so that the long attempts
+// do not use up the whole storage quota
+const MAX_STORED_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Frame {
+    pub delta_time: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<Event>,
+    /// Only present when the viewport was resized during this frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<Vector2<f64>>,
+    /// The last disruption was undone at the start of this frame, in the practice mode
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub undo: bool,
+}
+
+impl Frame {
+    // nothing happened in both, and they took just as long
+    fn repeats(&self, other: &Frame) -> bool {
+        self.events.is_empty()
+            && other.events.is_empty()
+            && self.size.is_none()
+            && other.size.is_none()
+            && !self.undo
+            && !other.undo
+            && self.delta_time == other.delta_time
+    }
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_one(repeat: &usize) -> bool {
+    *repeat == 1
+}
+
+// the frames are stored with the runs of the same empty frame kept only once,
+// without any events that is most of them, especially with a fixed timestep
+#[derive(Serialize, Deserialize)]
+struct FrameRun {
+    #[serde(flatten)]
+    frame: Frame,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    repeat: usize,
+}
+
+fn serialize_frames<S: Serializer>(frames: &[Frame], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut runs: Vec<FrameRun> = Vec::new();
+    for frame in frames {
+        match runs.last_mut() {
+            Some(run) if run.frame.repeats(frame) => run.repeat += 1,
+            _ => runs.push(FrameRun {
+                frame: frame.clone(),
+                repeat: 1,
+            }),
+        }
+    }
+    runs.serialize(serializer)
+}
+
+fn deserialize_frames<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Frame>, D::Error> {
+    let runs = Vec::<FrameRun>::deserialize(deserializer)?;
+    Ok(runs
+        .into_iter()
+        .flat_map(|run| vec![run.frame; run.repeat])
+        .collect())
+}
+
+/// Everything needed to reproduce a level attempt exactly,
+/// the level itself is included so that it survives changes to the levels file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub level_idx: usize,
+    pub level: GameLevel,
+    pub size: Vector2<f64>,
+    #[serde(
+        serialize_with = "serialize_frames",
+        deserialize_with = "deserialize_frames"
+    )]
+    pub frames: Vec<Frame>,
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+impl Recording {
+    pub fn new(level_idx: usize, level: GameLevel, size: Vector2<f64>) -> Self {
+        Self {
+            level_idx,
+            level,
+            size,
+            frames: Vec::new(),
+            score: None,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Whether it is small enough to be kept in the stored data
+    pub fn fits_in_storage(&self) -> bool {
+        self.to_json().len() <= MAX_STORED_SIZE
+    }
+
+    pub fn simulation(&self) -> Simulation {
+        let mut simulation = Simulation::new(self.level.clone(), self.size);
+        if self.frames.iter().any(|f| f.undo) {
+            simulation.keep_history();
+        }
+        simulation
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|f| f.delta_time).sum()
+    }
+
+    /// Applies the frame the same way the main game state does during the actual play
+    pub fn apply_frame(frame: &Frame, simulation: &mut Simulation) -> bool {
+        if let Some(size) = frame.size {
+            simulation.set_size(size);
+        }
+        if frame.undo {
+            simulation.undo();
+        }
+        for event in &frame.events {
+            if let Some(input) = Input::from_event(event) {
+                simulation.input(input);
+            }
+        }
+        simulation.update(frame.delta_time)
+    }
+
+    /// Runs the whole recording headlessly and returns the final status,
+    /// which can then be compared to the recorded score
+    pub fn verify(&self) -> Status {
+        let mut simulation = self.simulation();
+        for frame in &self.frames {
+            Self::apply_frame(frame, &mut simulation);
+        }
+        simulation.status()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match (self.verify(), self.score) {
+            (Status::Won { score }, Some(recorded)) => (score - recorded).abs() <= f64::EPSILON,
+            (Status::Won { .. }, None) => false,
+            (_, recorded) => recorded.is_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::event::MouseButton, testing::level};
+
+    const STEP: f64 = 1.0 / 60.0;
+
+    fn frame(events: Vec<Event>) -> Frame {
+        Frame {
+            delta_time: STEP,
+            events,
+            ..Frame::default()
+        }
+    }
+
+    // a single ring in the middle of the screen, cut across on the right of it
+    fn played() -> Recording {
+        let level = level(30.0, &[(0.1, 10.0)]);
+        let mut recording = Recording::new(0, level, [1000.0, 1000.0].into());
+        let mut frames = vec![frame(Vec::new()); 10];
+        frames.push(frame(vec![Event::MouseDown {
+            pos: [590.0, 500.0].into(),
+            button: MouseButton::Left,
+        }]));
+        frames.extend(vec![frame(Vec::new()); 5]);
+        frames.push(frame(vec![Event::MouseUp {
+            pos: [610.0, 500.0].into(),
+            button: MouseButton::Left,
+        }]));
+        frames.extend(vec![frame(Vec::new()); 10]);
+
+        let mut simulation = recording.simulation();
+        for frame in frames {
+            Recording::apply_frame(&frame, &mut simulation);
+            recording.frames.push(frame);
+        }
+        if let Status::Won { score } = simulation.status() {
+            recording.score = Some(score);
+        }
+        recording
+    }
+
+    #[test]
+    fn replays_to_the_same_score() {
+        let recording = played();
+        let score = recording.score.expect("the level is won");
+        assert_eq!(recording.verify(), Status::Won { score });
+        assert!(recording.is_valid());
+        assert!((recording.duration() - 27.0 * STEP).abs() < 1e-9);
+
+        let loaded = Recording::from_json(&recording.to_json()).unwrap();
+        assert_eq!(loaded.frames.len(), recording.frames.len());
+        assert_eq!(loaded.verify(), Status::Won { score });
+        assert!(loaded.is_valid());
+    }
+
+    #[test]
+    fn empty_frames_are_stored_once_per_run() {
+        let recording = played();
+        let json = recording.to_json();
+        // the three runs of the empty frames and the two with the events
+        assert_eq!(json.matches("delta_time").count(), 5);
+        assert!(recording.fits_in_storage());
+
+        let mut recording = recording;
+        recording.frames = (0..MAX_STORED_SIZE)
+            .map(|i| {
+                frame(vec![Event::MouseMove {
+                    pos: [i as f64, 0.0].into(),
+                    buttons: Vec::new(),
+                }])
+            })
+            .collect();
+        assert!(!recording.fits_in_storage());
+    }
+
+    #[test]
+    fn undone_frames_are_kept_apart() {
+        let mut recording = played();
+        recording.frames[5].undo = true;
+        let loaded = Recording::from_json(&recording.to_json()).unwrap();
+        let undone: Vec<_> = loaded.frames.iter().map(|f| f.undo).collect();
+        assert_eq!(
+            undone,
+            recording.frames.iter().map(|f| f.undo).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn tampered_recordings_are_invalid() {
+        let mut recording = played();
+        recording.score = recording.score.map(|score| score + 1.0);
+        assert!(!recording.is_valid());
+
+        // released later, so the cut takes more energy than was recorded
+        let mut recording = played();
+        let idx = recording
+            .frames
+            .iter()
+            .position(|f| !f.events.is_empty())
+            .unwrap();
+        recording.frames.insert(idx + 1, frame(Vec::new()));
+        assert!(!recording.is_valid());
+
+        // and without the cut the level is never won at all
+        let mut recording = played();
+        recording.frames.retain(|f| f.events.is_empty());
+        assert_eq!(recording.verify(), Status::Playing);
+        assert!(!recording.is_valid());
+    }
+}
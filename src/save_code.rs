@@ -0,0 +1,245 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    engine::versioned::{self, LoadError},
+    level::StoredData,
+};
+
+const PREFIX: &str = "QL";
+
+#[derive(Debug)]
+pub enum ImportError {
+    Format,
+    Checksum,
+    Data(LoadError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::Format => write!(f, "this is not a save code"),
+            ImportError::Checksum => {
+                write!(f, "the code is damaged, check that it was copied fully")
+            }
+            ImportError::Data(e) => write!(f, "the saved data is invalid: {}", e),
+        }
+    }
+}
+
+// FNV-1a, just to catch typos and truncated codes
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// The progress as a single line of text, without the best runs to keep it short
+pub fn export_code(data: &StoredData) -> String {
+    let data = StoredData {
+        best_runs: Vec::new(),
+        ..data.clone()
+    };
+    let json = serde_json::to_string(&data).unwrap();
+    format!(
+        "{}-{}-{:08x}",
+        PREFIX,
+        base64::encode_config(&json, base64::URL_SAFE_NO_PAD),
+        checksum(json.as_bytes())
+    )
+}
+
+/// The full data including the best runs, for saving as a file
+pub fn export_json(data: &StoredData) -> String {
+    serde_json::to_string_pretty(data).unwrap()
+}
+
+/// Accepts either a code from `export_code` or the contents of the exported file,
+/// older versions of the data are migrated the same way the stored data is
+pub fn import(text: &str) -> Result<StoredData, ImportError> {
+    let text = text.trim();
+    if text.starts_with('{') {
+        return versioned::load(text).map_err(ImportError::Data);
+    }
+
+    let mut parts = text.split('-');
+    let (payload, sum) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(PREFIX), Some(payload), Some(sum), None) => (payload, sum),
+        _ => return Err(ImportError::Format),
+    };
+    let json = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or(ImportError::Format)?;
+    if u32::from_str_radix(sum, 16).ok() != Some(checksum(json.as_bytes())) {
+        return Err(ImportError::Checksum);
+    }
+    versioned::load(&json).map_err(ImportError::Data)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreChange {
+    pub level_idx: usize,
+    pub current: f64,
+    pub imported: f64,
+}
+
+/// What importing would change, shown to the player before anything is overwritten
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveDiff {
+    pub unlocked_level: (usize, usize),
+    pub scores: Vec<ScoreChange>,
+}
+
+impl SaveDiff {
+    pub fn new(current: &StoredData, imported: &StoredData) -> Self {
+        let len = current.best_scores.len().max(imported.best_scores.len());
+        let scores = (0..len)
+            .map(|level_idx| ScoreChange {
+                level_idx,
+                current: current
+                    .best_scores
+                    .get(level_idx)
+                    .copied()
+                    .unwrap_or_default(),
+                imported: imported
+                    .best_scores
+                    .get(level_idx)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .filter(|change| (change.current - change.imported).abs() > f64::EPSILON)
+            .collect();
+        Self {
+            unlocked_level: (current.unlocked_level, imported.unlocked_level),
+            scores,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unlocked_level.0 == self.unlocked_level.1 && self.scores.is_empty()
+    }
+}
+
+/// Keeps the best of both, the settings stay as they are on this device
+pub fn merge(current: &StoredData, imported: &StoredData) -> StoredData {
+    let len = current.best_scores.len().max(imported.best_scores.len());
+    let mut best_scores = Vec::with_capacity(len);
+    let mut best_runs = Vec::with_capacity(len);
+    for idx in 0..len {
+        let score = |data: &StoredData| data.best_scores.get(idx).copied().unwrap_or_default();
+        let run = |data: &StoredData| data.best_runs.get(idx).cloned().flatten();
+        let from = if score(imported) > score(current) {
+            imported
+        } else {
+            current
+        };
+        best_scores.push(score(from));
+        best_runs.push(run(from));
+    }
+    // summing the stats would count them twice when the same code is imported again
+    let mut stats = current.stats.clone();
+    for (name, imported) in &imported.stats {
+        let entry = stats.entry(name.clone()).or_default();
+        if imported.attempts > entry.attempts {
+            *entry = imported.clone();
+        }
+    }
+    let mut custom_scores = current.custom_scores.clone();
+    for (key, score) in &imported.custom_scores {
+        let entry = custom_scores.entry(key.clone()).or_default();
+        *entry = entry.max(*score);
+    }
+    let mut custom_levels = current.custom_levels.clone();
+    for level in &imported.custom_levels {
+        if !custom_levels.iter().any(|l| l.name == level.name) {
+            custom_levels.push(level.clone());
+        }
+    }
+    let mut daily = current.daily.clone();
+    for (date, score) in &imported.daily.history {
+        let entry = daily.history.entry(date.clone()).or_default();
+        if score.unwrap_or_default() > entry.unwrap_or_default() {
+            *entry = *score;
+        }
+    }
+    daily.streak = daily.streak.max(imported.daily.streak);
+    daily.best_streak = daily.best_streak.max(imported.daily.best_streak);
+    let mut best_times = current.best_times.clone();
+    for (key, time) in &imported.best_times {
+        let entry = best_times.entry(key.clone()).or_insert(*time);
+        *entry = entry.min(*time);
+    }
+    StoredData {
+        passed_tutorial: current.passed_tutorial || imported.passed_tutorial,
+        unlocked_level: current.unlocked_level.max(imported.unlocked_level),
+        best_scores,
+        best_runs,
+        stats,
+        custom_scores,
+        custom_levels,
+        daily,
+        best_times,
+        ..current.clone()
+    }
+}
+
+/// Takes the imported progress as is, the settings stay as they are on this device
+pub fn replace(current: &StoredData, imported: &StoredData) -> StoredData {
+    StoredData {
+        version: current.version,
+        sounds_enabled: current.sounds_enabled,
+        music_enabled: current.music_enabled,
+        ..imported.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(unlocked_level: usize, best_scores: Vec<f64>) -> StoredData {
+        StoredData {
+            unlocked_level,
+            best_scores,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn code_roundtrips() {
+        let original = data(3, vec![90.5, 42.25, 0.0]);
+        let imported = import(&export_code(&original)).unwrap();
+        assert_eq!(imported.unlocked_level, 3);
+        assert_eq!(imported.best_scores, original.best_scores);
+    }
+
+    #[test]
+    fn damaged_codes_are_rejected() {
+        let code = export_code(&data(3, vec![90.5]));
+        let other = export_code(&data(4, vec![90.5]));
+        let damaged = format!("{}{}", &other[..other.len() - 8], &code[code.len() - 8..]);
+        assert!(matches!(import(&damaged), Err(ImportError::Checksum)));
+        assert!(matches!(import("hello"), Err(ImportError::Format)));
+    }
+
+    #[test]
+    fn exported_file_is_accepted() {
+        let imported = import(&export_json(&data(2, vec![10.0]))).unwrap();
+        assert_eq!(imported.unlocked_level, 2);
+    }
+
+    #[test]
+    fn merge_keeps_the_best() {
+        let current = data(2, vec![90.0, 50.0]);
+        let imported = data(1, vec![80.0, 60.0, 30.0]);
+
+        let diff = SaveDiff::new(&current, &imported);
+        assert_eq!(diff.unlocked_level, (2, 1));
+        assert_eq!(diff.scores.len(), 3);
+
+        let merged = merge(&current, &imported);
+        assert_eq!(merged.unlocked_level, 2);
+        assert_eq!(merged.best_scores, vec![90.0, 60.0, 30.0]);
+        assert!(SaveDiff::new(&merged, &merge(&merged, &imported)).is_empty());
+    }
+}
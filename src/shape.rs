@@ -0,0 +1,399 @@
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+// how many points of the curved outlines are used for the drawing
+const CURVE_SAMPLES: usize = 96;
+const CORNER_SAMPLES: usize = 8;
+
+/// A part of the outline between two angles from the center, in degrees,
+/// going from `start_angle` the same way as the particle
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub start_angle: f64,
+    pub end_angle: f64,
+}
+
+impl Span {
+    // the start and the length in radians, the length is within (0, TAU]
+    fn radians(self) -> (f64, f64) {
+        let length = (self.end_angle - self.start_angle).rem_euclid(360.0);
+        let length = if length == 0.0 { 360.0 } else { length };
+        (self.start_angle.to_radians(), length.to_radians())
+    }
+}
+
+/// The parts of the outline that are there, as the starts and the ends in radians,
+/// the whole outline when there is no `arc`, minus all of the `gaps`
+pub fn drawn_spans(arc: Option<Span>, gaps: &[Span]) -> Vec<(f64, f64)> {
+    let (start, length) = arc.map_or((0.0, TAU), Span::radians);
+    // everything is relative to the start, so that the first part begins at zero
+    let mut spans = vec![(0.0, length)];
+    for gap in gaps {
+        let (gap_start, gap_length) = gap.radians();
+        let gap_start = (gap_start - start).rem_euclid(TAU);
+        // a gap can wrap around past the start
+        for &from in &[gap_start, gap_start - TAU] {
+            let to = from + gap_length;
+            spans = spans
+                .into_iter()
+                .flat_map(|(s, e): (f64, f64)| {
+                    let mut left = Vec::new();
+                    if s < from {
+                        left.push((s, e.min(from)));
+                    }
+                    if e > to {
+                        left.push((s.max(to), e));
+                    }
+                    left
+                })
+                .collect();
+        }
+    }
+    spans
+        .into_iter()
+        .map(|(s, e)| (start + s, start + e))
+        .collect()
+}
+
+/// Whether the angle in radians falls inside one of the spans from `drawn_spans`
+pub fn is_drawn(spans: &[(f64, f64)], angle: f64) -> bool {
+    spans.iter().any(|&(start, end)| {
+        let relative = (angle - start).rem_euclid(TAU);
+        relative <= end - start
+    })
+}
+
+/// The outline of a ring, in units of its radius, all the rotations are in degrees
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Shape {
+    #[default]
+    Circle,
+    /// The radius is half the width, `ratio` is the height to the width
+    Ellipse {
+        ratio: f64,
+        #[serde(default)]
+        rotation: f64,
+    },
+    /// With the vertices at the radius
+    Polygon {
+        sides: u32,
+        #[serde(default)]
+        rotation: f64,
+    },
+    /// The radius is half the width, the `corner` radius is relative to it too
+    RoundedRect {
+        ratio: f64,
+        corner: f64,
+        #[serde(default)]
+        rotation: f64,
+    },
+}
+
+pub(crate) fn cross(a: Vector2<f64>, b: Vector2<f64>) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+fn rotate(p: Vector2<f64>, angle: f64) -> Vector2<f64> {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(p.x * cos - p.y * sin, p.x * sin + p.y * cos)
+}
+
+/// Where the segment from `a` to `b` crosses the one from `c` to `d`
+pub(crate) fn segment_hit(
+    a: Vector2<f64>,
+    b: Vector2<f64>,
+    c: Vector2<f64>,
+    d: Vector2<f64>,
+) -> Option<Vector2<f64>> {
+    let ab = b - a;
+    let cd = d - c;
+    let denom = cross(ab, cd);
+    if denom == 0.0 {
+        return None;
+    }
+    let t = cross(c - a, cd) / denom;
+    let u = cross(c - a, ab) / denom;
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some(a + ab * t)
+    } else {
+        None
+    }
+}
+
+// the points where the segment crosses the circle
+fn circle_hits(
+    a: Vector2<f64>,
+    b: Vector2<f64>,
+    center: Vector2<f64>,
+    radius: f64,
+) -> Vec<Vector2<f64>> {
+    let d = b - a;
+    let f = a - center;
+    let qa = d.dot(&d);
+    let qb = 2.0 * f.dot(&d);
+    let qc = f.dot(&f) - radius * radius;
+    let disc = qb * qb - 4.0 * qa * qc;
+    if qa == 0.0 || disc < 0.0 {
+        return Vec::new();
+    }
+    let sqrt = disc.sqrt();
+    [(-qb - sqrt) / (2.0 * qa), (-qb + sqrt) / (2.0 * qa)]
+        .iter()
+        .filter(|t| (0.0..=1.0).contains(*t))
+        .map(|t| a + d * *t)
+        .collect()
+}
+
+impl Shape {
+    pub fn is_circle(&self) -> bool {
+        *self == Shape::Circle
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Circle => "circle",
+            Shape::Ellipse { .. } => "ellipse",
+            Shape::Polygon { .. } => "polygon",
+            Shape::RoundedRect { .. } => "rounded rect",
+        }
+    }
+
+    fn rotation(&self) -> f64 {
+        match self {
+            Shape::Circle => 0.0,
+            Shape::Ellipse { rotation, .. }
+            | Shape::Polygon { rotation, .. }
+            | Shape::RoundedRect { rotation, .. } => rotation.to_radians(),
+        }
+    }
+
+    /// Whether the segment, in units of the radius from the center, crosses the outline
+    pub fn crosses(&self, a: Vector2<f64>, b: Vector2<f64>) -> bool {
+        !self.hits(a, b).is_empty()
+    }
+
+    /// The points where the segment crosses the outline, in the same units as `crosses`
+    pub fn hits(&self, a: Vector2<f64>, b: Vector2<f64>) -> Vec<Vector2<f64>> {
+        let rotation = self.rotation();
+        let a = rotate(a, -rotation);
+        let b = rotate(b, -rotation);
+        let hits =
+            match *self {
+                Shape::Circle => circle_hits(a, b, Vector2::zeros(), 1.0),
+                Shape::Ellipse { ratio, .. } => {
+                    let squash = Vector2::new(1.0, 1.0 / ratio);
+                    let a = a.component_mul(&squash);
+                    let b = b.component_mul(&squash);
+                    circle_hits(a, b, Vector2::zeros(), 1.0)
+                        .into_iter()
+                        .map(|p| Vector2::new(p.x, p.y * ratio))
+                        .collect()
+                }
+                Shape::Polygon { .. } => {
+                    // the rotation is already undone, so the outline is taken without it
+                    let vertices = self.local_outline();
+                    (0..vertices.len())
+                        .filter_map(|i| {
+                            let next = vertices[(i + 1) % vertices.len()];
+                            segment_hit(a, b, vertices[i], next)
+                        })
+                        .collect()
+                }
+                Shape::RoundedRect { ratio, corner, .. } => {
+                    let corner = corner.min(1.0).min(ratio);
+                    let (w, h) = (1.0 - corner, ratio - corner);
+                    let edges = [
+                        ([-w, ratio], [w, ratio]),
+                        ([-w, -ratio], [w, -ratio]),
+                        ([1.0, -h], [1.0, h]),
+                        ([-1.0, -h], [-1.0, h]),
+                    ];
+                    let mut hits = edges
+                        .iter()
+                        .filter_map(|&(c, d)| segment_hit(a, b, c.into(), d.into()))
+                        .collect::<Vec<_>>();
+                    for &(sx, sy) in &[(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)] {
+                        let center = Vector2::new(sx * w, sy * h);
+                        hits.extend(circle_hits(a, b, center, corner).into_iter().filter(|p| {
+                            sx * (p.x - center.x) >= 0.0 && sy * (p.y - center.y) >= 0.0
+                        }));
+                    }
+                    hits
+                }
+            };
+        hits.into_iter().map(|p| rotate(p, rotation)).collect()
+    }
+
+    // the outline without the rotation, as a closed polyline
+    fn local_outline(&self) -> Vec<Vector2<f64>> {
+        match *self {
+            Shape::Circle => (0..CURVE_SAMPLES)
+                .map(|i| {
+                    let angle = TAU * i as f64 / CURVE_SAMPLES as f64;
+                    Vector2::new(angle.cos(), angle.sin())
+                })
+                .collect(),
+            Shape::Ellipse { ratio, .. } => Shape::Circle
+                .local_outline()
+                .into_iter()
+                .map(|p| Vector2::new(p.x, p.y * ratio))
+                .collect(),
+            Shape::Polygon { sides, .. } => (0..sides)
+                .map(|i| {
+                    // so that with no rotation the polygons stand on a side
+                    let angle = FRAC_PI_2 + PI / sides as f64 + TAU * i as f64 / sides as f64;
+                    Vector2::new(angle.cos(), angle.sin())
+                })
+                .collect(),
+            Shape::RoundedRect { ratio, corner, .. } => {
+                let corner = corner.min(1.0).min(ratio);
+                let (w, h) = (1.0 - corner, ratio - corner);
+                let corners = [(w, h), (-w, h), (-w, -h), (w, -h)];
+                corners
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(quarter, &(x, y))| {
+                        (0..=CORNER_SAMPLES).map(move |i| {
+                            let angle =
+                                FRAC_PI_2 * (quarter as f64 + i as f64 / CORNER_SAMPLES as f64);
+                            Vector2::new(x + corner * angle.cos(), y + corner * angle.sin())
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// A closed polyline along the outline, in units of the radius
+    pub fn outline(&self) -> Vec<Vector2<f64>> {
+        let rotation = self.rotation();
+        self.local_outline()
+            .into_iter()
+            .map(|p| rotate(p, rotation))
+            .collect()
+    }
+
+    /// The point of the outline in the direction of the angle from the center
+    pub fn point_at(&self, angle: f64) -> Vector2<f64> {
+        let dir = Vector2::new(angle.cos(), angle.sin());
+        if self.is_circle() {
+            return dir;
+        }
+        // the outline surrounds the center, so the ray crosses it exactly once
+        let far = dir * 4.0 * self.extent().max();
+        let outline = self.outline();
+        (0..outline.len())
+            .find_map(|i| {
+                let c = outline[i];
+                let d = outline[(i + 1) % outline.len()];
+                let denom = cross(far, d - c);
+                if denom == 0.0 {
+                    return None;
+                }
+                let t = cross(c, d - c) / denom;
+                let u = cross(c, far) / denom;
+                if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                    Some(far * t)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(dir)
+    }
+
+    /// Half the size of the bounding box, in units of the radius
+    pub fn extent(&self) -> Vector2<f64> {
+        self.outline()
+            .iter()
+            .fold(Vector2::zeros(), |acc, p| acc.sup(&p.abs()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f64, y: f64) -> Vector2<f64> {
+        Vector2::new(x, y)
+    }
+
+    #[test]
+    fn circle_chords_cross() {
+        let circle = Shape::Circle;
+        assert!(circle.crosses(v(0.5, 0.0), v(1.5, 0.0)));
+        // both ends outside, but it goes through
+        assert!(circle.crosses(v(-2.0, 0.5), v(2.0, 0.5)));
+        assert!(!circle.crosses(v(-0.5, 0.0), v(0.5, 0.0)));
+        assert!(!circle.crosses(v(-2.0, 1.5), v(2.0, 1.5)));
+    }
+
+    #[test]
+    fn rotated_ellipse() {
+        let ellipse = Shape::Ellipse {
+            ratio: 0.5,
+            rotation: 90.0,
+        };
+        // the long side now goes along y
+        assert!(ellipse.crosses(v(0.0, 0.9), v(0.0, 1.1)));
+        assert!(!ellipse.crosses(v(0.6, 0.0), v(0.9, 0.0)));
+        assert!(ellipse.crosses(v(0.4, 0.0), v(0.6, 0.0)));
+        assert!((ellipse.extent() - v(0.5, 1.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn polygon_edges() {
+        let square = Shape::Polygon {
+            sides: 4,
+            rotation: 0.0,
+        };
+        let half = 0.5f64.sqrt();
+        // standing on a side, so the sides are at the half diagonal
+        assert!(square.crosses(v(0.0, half - 0.01), v(0.0, half + 0.01)));
+        assert!(!square.crosses(v(0.0, half + 0.01), v(0.0, half + 0.1)));
+        assert!((square.point_at(0.0) - v(half, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn gaps_are_cut_out_of_the_arc() {
+        let arc = Span {
+            start_angle: 0.0,
+            end_angle: 180.0,
+        };
+        let gaps = [
+            Span {
+                start_angle: 80.0,
+                end_angle: 100.0,
+            },
+            // wraps around the start of the arc
+            Span {
+                start_angle: 350.0,
+                end_angle: 10.0,
+            },
+        ];
+        let spans = drawn_spans(Some(arc), &gaps);
+        assert_eq!(spans.len(), 2);
+        assert!(is_drawn(&spans, 45f64.to_radians()));
+        assert!(is_drawn(&spans, 135f64.to_radians()));
+        assert!(!is_drawn(&spans, 5f64.to_radians()));
+        assert!(!is_drawn(&spans, 90f64.to_radians()));
+        assert!(!is_drawn(&spans, 270f64.to_radians()));
+        assert!(is_drawn(&drawn_spans(None, &[]), 4.0));
+    }
+
+    #[test]
+    fn rounded_rect_corners() {
+        let rect = Shape::RoundedRect {
+            ratio: 0.5,
+            corner: 0.2,
+            rotation: 0.0,
+        };
+        assert!(rect.crosses(v(0.0, 0.4), v(0.0, 0.6)));
+        assert!(rect.crosses(v(0.9, 0.0), v(1.1, 0.0)));
+        // the square corner would be hit, the rounded one is not
+        assert!(!rect.crosses(v(0.97, 0.47), v(1.1, 0.6)));
+        assert!(rect.crosses(v(0.8, 0.3), v(1.1, 0.6)));
+    }
+}
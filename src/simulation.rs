@@ -0,0 +1,747 @@
+use std::f64::consts::{PI, TAU};
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::event::{Event, MouseButton},
+    level::{CutRule, EnergyRing, GameLevel},
+};
+
+pub const JIGGLE_TIME: f64 = 0.25;
+
+const POWER_USED_PER_PIXEL_PER_SECOND: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisruptionCause {
+    Mouse,
+    Touch,
+}
+
+#[derive(Debug, Clone)]
+pub struct Disruption {
+    pub start: Vector2<f64>,
+    pub end: Vector2<f64>,
+    pub start_time: f64,
+    pub cause: DisruptionCause,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Playing,
+    Won { score: f64 },
+    Lost,
+}
+
+/// Player input, already decoupled from whatever DOM event caused it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Start {
+        pos: Vector2<f64>,
+        cause: DisruptionCause,
+    },
+    Move {
+        pos: Vector2<f64>,
+        dragging: bool,
+        cause: DisruptionCause,
+    },
+    Finish {
+        pos: Option<Vector2<f64>>,
+        cause: DisruptionCause,
+    },
+}
+
+impl Input {
+    pub fn from_event(event: &Event) -> Option<Input> {
+        Some(match event {
+            Event::MouseDown {
+                pos,
+                button: MouseButton::Left,
+            } => Input::Start {
+                pos: *pos,
+                cause: DisruptionCause::Mouse,
+            },
+            Event::MouseMove { pos, buttons } => Input::Move {
+                pos: *pos,
+                dragging: buttons.contains(&MouseButton::Left),
+                cause: DisruptionCause::Mouse,
+            },
+            Event::MouseUp {
+                pos,
+                button: MouseButton::Left,
+            } => Input::Finish {
+                pos: Some(*pos),
+                cause: DisruptionCause::Mouse,
+            },
+            Event::TouchStart { touches } if touches.len() == 1 => Input::Start {
+                pos: touches[0],
+                cause: DisruptionCause::Touch,
+            },
+            Event::TouchMove { touches } if touches.len() == 1 => Input::Move {
+                pos: touches[0],
+                dragging: true,
+                cause: DisruptionCause::Touch,
+            },
+            Event::TouchEnd { touches } if touches.len() <= 1 => Input::Finish {
+                pos: touches.get(0).copied(),
+                cause: DisruptionCause::Touch,
+            },
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedInput {
+    pub time: f64,
+    pub input: Input,
+}
+
+#[derive(Debug, Clone)]
+pub struct Particle {
+    /// `None` once it has escaped
+    pub ring: Option<usize>,
+    pub angle: f64,
+    speed: f64,
+    // one or minus one, flipped by the rings that reverse it
+    direction: f64,
+}
+
+/// Everything a finished disruption changes, taken right before it,
+/// the clock is not in there since undoing does not turn it back
+#[derive(Debug, Clone)]
+struct Snapshot {
+    energy: f64,
+    disrupted_times: Vec<f64>,
+    particles: Vec<Particle>,
+    disruptions: u32,
+    jiggles: u32,
+}
+
+/// The actual rules of the game, without any rendering or browser APIs,
+/// so that it can be advanced deterministically with any timestep
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    level: GameLevel,
+    size: Vector2<f64>,
+    time: f64,
+    energy: f64,
+    particles: Vec<Particle>,
+    disruption: Option<Disruption>,
+    status: Status,
+    disruptions: u32,
+    jiggles: u32,
+    // only kept when the disruptions can be undone
+    history: Option<Vec<Snapshot>>,
+}
+
+// the ring with the most energy that is there, between rings
+// with just as much the particle stays on the current one
+fn best_ring(rings: &[EnergyRing], current: Option<usize>) -> Option<usize> {
+    rings
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.disrupted_time <= 0.0)
+        .max_by(|(idx1, r1), (idx2, r2)| {
+            r1.base_energy
+                .partial_cmp(&r2.base_energy)
+                .expect("NaN not allowed")
+                .then_with(|| (Some(*idx1) == current).cmp(&(Some(*idx2) == current)))
+        })
+        .map(|(idx, _)| idx)
+}
+
+impl Simulation {
+    pub fn new(mut level: GameLevel, size: Vector2<f64>) -> Self {
+        for ring in &mut level.rings {
+            ring.disrupted_time = 0.0;
+        }
+        let particles = level
+            .particle_starts()
+            .into_iter()
+            .map(|start| Particle {
+                ring: start
+                    .ring
+                    .filter(|&ring| ring < level.rings.len())
+                    .or_else(|| best_ring(&level.rings, None)),
+                angle: 0.0,
+                speed: start.speed,
+                direction: 1.0,
+            })
+            .collect();
+        Self {
+            energy: level.energy,
+            level,
+            size,
+            time: 0.0,
+            particles,
+            disruption: None,
+            status: Status::Playing,
+            disruptions: 0,
+            jiggles: 0,
+            history: None,
+        }
+    }
+
+    pub fn level(&self) -> &GameLevel {
+        &self.level
+    }
+
+    pub fn size(&self) -> Vector2<f64> {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: Vector2<f64>) {
+        self.size = size;
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Whether a cut across the ring at the angle around its center is on the side
+    /// that the rule of the ring asks for, of any particle on it (if there is one)
+    pub fn cut_allowed(&self, ring: usize, angle: f64) -> bool {
+        let rule = self.level.rings[ring].cut_rule;
+        if rule == CutRule::Anywhere {
+            return true;
+        }
+        let ring_speed = self.level.rings[ring].speed;
+        let mut on_it = self
+            .particles
+            .iter()
+            .filter(|p| p.ring == Some(ring))
+            .peekable();
+        if on_it.peek().is_none() {
+            return true;
+        }
+        on_it.any(|p| {
+            // the way the particle goes around, with the speeds of both and the reversals
+            let turning = (p.speed * ring_speed).signum() * p.direction;
+            let ahead = ((angle - p.angle) * turning).rem_euclid(TAU) < PI;
+            ahead == (rule == CutRule::Ahead)
+        })
+    }
+
+    pub fn disruption(&self) -> Option<&Disruption> {
+        self.disruption.as_ref()
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn energy_spent(&self) -> f64 {
+        self.level.energy - self.energy
+    }
+
+    /// How many disruptions were finished so far
+    pub fn disruptions(&self) -> u32 {
+        self.disruptions
+    }
+
+    /// How many times a ring without a particle on it was hit on its own
+    pub fn jiggles(&self) -> u32 {
+        self.jiggles
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.status == Status::Playing
+    }
+
+    pub fn jiggling(&self) -> bool {
+        self.level
+            .rings
+            .iter()
+            .any(|r| r.disrupted_time > 0.0 && r.disrupted_time <= JIGGLE_TIME)
+    }
+
+    /// Starts keeping a snapshot before every finished disruption, so that they can be undone
+    pub fn keep_history(&mut self) {
+        if self.history.is_none() {
+            self.history = Some(Vec::new());
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        matches!(&self.history, Some(history) if !history.is_empty())
+    }
+
+    /// Goes back to right before the last finished disruption, even when the level was lost
+    /// because of it, while the time goes on, returns false when there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let snapshot = match self.history.as_mut().and_then(Vec::pop) {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        self.energy = snapshot.energy;
+        for (ring, disrupted_time) in self.level.rings.iter_mut().zip(snapshot.disrupted_times) {
+            ring.disrupted_time = disrupted_time;
+        }
+        // they go on from where they are now, only back on the rings they were on
+        for (particle, before) in self.particles.iter_mut().zip(snapshot.particles) {
+            particle.ring = before.ring;
+            particle.direction = before.direction;
+        }
+        self.disruptions = snapshot.disruptions;
+        self.jiggles = snapshot.jiggles;
+        self.disruption = None;
+        self.status = Status::Playing;
+        true
+    }
+
+    pub fn input(&mut self, input: Input) {
+        if !self.is_playing() {
+            return;
+        }
+        match input {
+            Input::Start { pos, cause } => {
+                self.disruption = Some(Disruption {
+                    start: pos,
+                    end: pos,
+                    start_time: self.time,
+                    cause,
+                })
+            }
+            Input::Move {
+                pos,
+                dragging,
+                cause,
+            } => {
+                if let Some(d) = self.disruption.as_mut().filter(|d| d.cause == cause) {
+                    if dragging {
+                        d.end = pos;
+                    } else if self.time - d.start_time >= 0.01 {
+                        self.finish_disruption(Some(pos));
+                    }
+                }
+            }
+            Input::Finish { pos, cause } => {
+                if self.disruption.as_ref().map(|d| d.cause) == Some(cause) {
+                    self.finish_disruption(pos)
+                }
+            }
+        }
+    }
+
+    fn finish_disruption(&mut self, pos: Option<Vector2<f64>>) {
+        if let Some(mut d) = self.disruption.take() {
+            if let Some(pos) = pos {
+                d.end = pos;
+            }
+
+            if let Some(history) = self.history.as_mut() {
+                history.push(Snapshot {
+                    energy: self.energy,
+                    disrupted_times: self.level.rings.iter().map(|r| r.disrupted_time).collect(),
+                    particles: self.particles.clone(),
+                    disruptions: self.disruptions,
+                    jiggles: self.jiggles,
+                });
+            }
+
+            let center = self.size / 2.0;
+
+            let dist = d.start.metric_distance(&d.end);
+            let time = self.time - d.start_time;
+
+            let crossed = self
+                .level
+                .obstacles
+                .iter()
+                .filter(|o| o.crossed_by(center, self.time, &d))
+                .collect::<Vec<_>>();
+            let blocked = crossed.iter().any(|o| o.blocks());
+            let surcharge = crossed.iter().filter_map(|o| o.surcharge).sum::<f64>();
+
+            // the moving rings are hit where they are when the cut is finished
+            let offsets = self.level.ring_offsets(self.time);
+            // a blocked cut still costs the energy, it just does not reach any ring
+            let hits = self
+                .level
+                .rings
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| !blocked && r.disrupted_time <= 1.0)
+                .map(|(idx, r)| (idx, r.hit_angles(center, offsets[idx], &d)))
+                .filter(|(_, angles)| !angles.is_empty())
+                .collect::<Vec<_>>();
+            // the rings with a particle on them, cut where their rules ask for
+            let ready = hits
+                .iter()
+                .filter(|(idx, angles)| {
+                    self.particles.iter().any(|p| p.ring == Some(*idx))
+                        && angles.iter().any(|&angle| self.cut_allowed(*idx, angle))
+                })
+                .map(|(idx, _)| *idx)
+                .collect::<Vec<_>>();
+            let mut intersections = self
+                .level
+                .rings
+                .iter_mut()
+                .enumerate()
+                .filter(|(idx, _)| hits.iter().any(|(hit, _)| hit == idx))
+                .collect::<Vec<_>>();
+
+            let links = &self.level.links;
+            let group = links.iter().find(|group| {
+                !group.is_empty()
+                    && group.len() == intersections.len()
+                    && intersections.iter().all(|(idx, _)| group.contains(idx))
+            });
+
+            let mut jiggled = false;
+            let extras = if let Some(group) = group {
+                // like a single ring, the group only goes with a particle on it
+                let disrupt = group.iter().any(|ring| ready.contains(ring));
+                for (_, ring) in &mut intersections {
+                    ring.disrupted_time = if disrupt {
+                        ring.restore_time
+                    } else {
+                        JIGGLE_TIME
+                    };
+                }
+                jiggled = !disrupt;
+                intersections.iter().map(|(_, r)| r.base_energy).sum()
+            } else if intersections.len() == 1 {
+                let (idx, ring) = &mut intersections[0];
+                // the linked rings only go together with the rest of their group
+                let linked = links.iter().any(|group| group.contains(idx));
+                ring.disrupted_time = if ready.contains(idx) && !linked {
+                    ring.restore_time
+                } else {
+                    jiggled = true;
+                    JIGGLE_TIME
+                };
+                ring.base_energy
+            } else {
+                intersections.iter_mut().map(|(_, r)| r.base_energy).sum()
+            };
+
+            self.energy -= dist * time * POWER_USED_PER_PIXEL_PER_SECOND + extras + surcharge;
+            self.disruptions += 1;
+            if jiggled {
+                self.jiggles += 1;
+            }
+        }
+    }
+
+    /// Every particle jumps to the ring with the most energy it can reach,
+    /// returns true when none of them has a ring left to stay on
+    fn update_particle_levels(&mut self) -> bool {
+        let rings = &self.level.rings;
+        for particle in &mut self.particles {
+            if particle.ring.is_none() {
+                continue;
+            }
+            particle.ring = best_ring(rings, particle.ring);
+        }
+        self.particles.iter().all(|p| p.ring.is_none())
+    }
+
+    /// Advances the simulation by the given time, returns true if any particle jumped
+    pub fn update(&mut self, delta_time: f64) -> bool {
+        if !self.is_playing() {
+            return false;
+        }
+        self.time += delta_time;
+
+        for ring in &mut self.level.rings {
+            if ring.disrupted_time > 0.0 {
+                ring.disrupted_time -= delta_time;
+            }
+        }
+
+        if self.energy <= 0.0 {
+            self.status = Status::Lost;
+            return false;
+        }
+
+        let prev_rings = self.particles.iter().map(|p| p.ring).collect::<Vec<_>>();
+        if self.update_particle_levels() {
+            let free =
+                self.level.energy - self.level.rings.iter().map(|r| r.base_energy).sum::<f64>();
+
+            self.status = Status::Won {
+                score: (1.0 - (free - self.energy) / free) * 100.0,
+            };
+            return false;
+        }
+        let rings = &self.level.rings;
+        for (particle, prev) in self.particles.iter_mut().zip(&prev_rings) {
+            let ring = match particle.ring {
+                Some(ring) => ring,
+                None => continue,
+            };
+            if rings[ring].reverse && *prev != Some(ring) {
+                particle.direction = -particle.direction;
+            }
+            particle.angle +=
+                TAU * particle.speed * rings[ring].speed * particle.direction * delta_time;
+        }
+
+        // escaping is not a jump
+        self.particles
+            .iter()
+            .zip(prev_rings)
+            .any(|(p, prev)| p.ring.is_some() && p.ring != prev)
+    }
+
+    /// Feeds the timestamped inputs in order, advancing with a fixed step
+    /// in between, and keeps going until the level is either won or lost
+    /// or the `max_time` is reached
+    pub fn run(
+        &mut self,
+        inputs: impl IntoIterator<Item = TimedInput>,
+        step: f64,
+        max_time: f64,
+    ) -> Status {
+        for TimedInput { time, input } in inputs {
+            while self.is_playing() && self.time + step <= time {
+                self.update(step);
+            }
+            self.input(input);
+        }
+        while self.is_playing() && self.time < max_time {
+            self.update(step);
+        }
+        self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::{cut, cut_now, level, SIZE, STEP};
+
+    #[test]
+    fn particle_jumps_down_and_back_up() {
+        let mut simulation = Simulation::new(level(100.0, &[(0.1, 10.0), (0.2, 5.0)]), SIZE.into());
+        assert_eq!(simulation.particles()[0].ring, Some(0));
+
+        cut_now(&mut simulation, 90.0, 110.0);
+        assert_eq!(simulation.level().rings[0].disrupted_time, 3.0);
+        assert!(simulation.update(STEP));
+        assert_eq!(simulation.particles()[0].ring, Some(1));
+        assert_eq!(simulation.disruptions(), 1);
+        assert_eq!(simulation.jiggles(), 0);
+
+        // the ring comes back after its restore time, and the particle with it
+        while simulation.level().rings[0].disrupted_time > 0.0 {
+            assert_eq!(simulation.particles()[0].ring, Some(1));
+            simulation.update(STEP);
+        }
+        simulation.update(STEP);
+        assert_eq!(simulation.particles()[0].ring, Some(0));
+        assert!(simulation.is_playing());
+    }
+
+    #[test]
+    fn particles_start_on_their_own_rings() {
+        let mut level = level(100.0, &[(0.1, 10.0), (0.2, 10.0), (0.3, 5.0)]);
+        level.particles =
+            serde_json::from_value(json!([{ "ring": 0 }, { "ring": 1 }, { "ring": 2 }])).unwrap();
+        let mut simulation = Simulation::new(level, SIZE.into());
+        let rings = |simulation: &Simulation| {
+            simulation
+                .particles()
+                .iter()
+                .map(|p| p.ring)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rings(&simulation), vec![Some(0), Some(1), Some(2)]);
+
+        // the ones on a ring with the most energy stay there, the last one jumps up
+        simulation.update(STEP);
+        assert_eq!(rings(&simulation), vec![Some(0), Some(1), Some(1)]);
+
+        // and they go over to the other ring with as much energy, and stay there
+        cut_now(&mut simulation, 190.0, 210.0);
+        simulation.update(STEP);
+        assert_eq!(rings(&simulation), vec![Some(0), Some(0), Some(0)]);
+        while simulation.level().rings[1].disrupted_time > 0.0 {
+            simulation.update(STEP);
+        }
+        simulation.update(STEP);
+        assert_eq!(rings(&simulation), vec![Some(0), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn empty_ring_only_jiggles() {
+        let mut simulation = Simulation::new(level(100.0, &[(0.1, 10.0), (0.2, 5.0)]), SIZE.into());
+        cut_now(&mut simulation, 190.0, 210.0);
+        assert_eq!(simulation.level().rings[1].disrupted_time, JIGGLE_TIME);
+        assert!(simulation.jiggling());
+        assert_eq!(simulation.jiggles(), 1);
+        assert!(!simulation.update(STEP));
+        assert_eq!(simulation.particles()[0].ring, Some(0));
+        // the base energy is still paid
+        assert_eq!(simulation.energy(), 95.0);
+    }
+
+    #[test]
+    fn energy_drains_with_the_length_and_duration_of_the_cut() {
+        let mut simulation = Simulation::new(level(100.0, &[(0.1, 10.0), (0.2, 5.0)]), SIZE.into());
+        let status = simulation.run(cut(0.0, 0.5, 90.0, 110.0), STEP, 0.5);
+        assert_eq!(status, Status::Playing);
+        // 20 pixels held for half a second, and the base energy of the ring
+        assert!((simulation.energy() - 80.0).abs() < 1e-9);
+        assert!((simulation.energy_spent() - 20.0).abs() < 1e-9);
+        assert!((simulation.time() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn escaping_wins_with_the_efficiency_as_the_score() {
+        let mut simulation = Simulation::new(level(30.0, &[(0.1, 10.0)]), SIZE.into());
+        let status = simulation.run(cut(0.0, 0.5, 90.0, 110.0), STEP, 1.0);
+        // 20 of the free energy, of which 10 went to the length of the cut
+        match status {
+            Status::Won { score } => assert!((score - 50.0).abs() < 1e-9),
+            _ => panic!("not won: {:?}", status),
+        }
+
+        let mut simulation = Simulation::new(level(30.0, &[(0.1, 10.0)]), SIZE.into());
+        let status = simulation.run(cut(0.0, 0.0, 90.0, 110.0), STEP, 1.0);
+        assert_eq!(status, Status::Won { score: 100.0 });
+    }
+
+    #[test]
+    fn running_out_of_energy_loses() {
+        let mut simulation = Simulation::new(level(8.0, &[(0.1, 10.0), (0.2, 5.0)]), SIZE.into());
+        cut_now(&mut simulation, 190.0, 210.0);
+        simulation.update(STEP);
+        assert!(simulation.is_playing());
+        cut_now(&mut simulation, 190.0, 210.0);
+        simulation.update(STEP);
+        assert_eq!(simulation.status(), Status::Lost);
+
+        // nothing happens after that
+        cut_now(&mut simulation, 90.0, 110.0);
+        assert!(!simulation.update(STEP));
+        assert_eq!(simulation.disruptions(), 2);
+        assert_eq!(simulation.status(), Status::Lost);
+    }
+
+    #[test]
+    fn undo_goes_back_before_the_cut() {
+        let mut simulation = Simulation::new(level(12.0, &[(0.1, 10.0), (0.2, 5.0)]), SIZE.into());
+        simulation.keep_history();
+        // the outer ring only jiggles, then the inner one takes more than what is left
+        cut_now(&mut simulation, 190.0, 210.0);
+        simulation.update(0.1);
+        cut_now(&mut simulation, 90.0, 110.0);
+        simulation.update(0.1);
+        assert_eq!(simulation.status(), Status::Lost);
+
+        assert!(simulation.undo());
+        assert!(simulation.is_playing());
+        assert_eq!(simulation.energy(), 7.0);
+        assert!(simulation.level().rings[0].disrupted_time <= 0.0);
+        assert_eq!(simulation.particles()[0].ring, Some(0));
+
+        assert!(simulation.undo());
+        assert_eq!(simulation.energy(), 12.0);
+        assert_eq!(simulation.jiggles(), 0);
+        assert!(!simulation.can_undo() && !simulation.undo());
+        assert_eq!(simulation.time(), 0.2);
+    }
+
+    #[test]
+    fn moving_rings_go_on_after_an_undo() {
+        let level = serde_json::from_value(json!({
+            "name": "a",
+            "energy": 100,
+            "rings": [
+                { "radius": 0.1, "base_energy": 10,
+                  "motion": { "type": "ping-pong", "to": [0.2, 0], "period": 2 } },
+                { "radius": 0.4, "base_energy": 5 }
+            ]
+        }))
+        .unwrap();
+        let mut simulation = Simulation::new(level, SIZE.into());
+        simulation.keep_history();
+        // half way, so the inner ring is 200 pixels to the right
+        simulation.update(1.0);
+        cut_now(&mut simulation, 290.0, 310.0);
+        simulation.update(STEP);
+        assert_eq!(simulation.particles()[0].ring, Some(1));
+
+        assert!(simulation.undo());
+        assert_eq!(simulation.time(), 1.0 + STEP);
+        assert_eq!(simulation.particles()[0].ring, Some(0));
+        // the left side of where the ring was at the start is empty by now
+        cut_now(&mut simulation, -110.0, -90.0);
+        simulation.update(STEP);
+        assert_eq!(simulation.particles()[0].ring, Some(0));
+        cut_now(&mut simulation, 290.0, 310.0);
+        simulation.update(STEP);
+        assert_eq!(simulation.particles()[0].ring, Some(1));
+    }
+
+    // a short radial cut across the ring at the angle, after a quarter turn at the given speeds
+    fn cut_behind_rule(ring_speed: f64, particle_speed: f64, angle: f64) -> Simulation {
+        let level = serde_json::from_value(json!({
+            "name": "a",
+            "energy": 100,
+            "rings": [{ "radius": 0.1, "base_energy": 10, "speed": ring_speed, "cut_rule": "behind" }],
+            "particles": [{ "speed": particle_speed }]
+        }))
+        .unwrap();
+        let mut simulation = Simulation::new(level, SIZE.into());
+        simulation.update(0.5);
+        let dir = Vector2::new(angle.cos(), angle.sin());
+        let center = Vector2::new(500.0, 500.0);
+        let cause = DisruptionCause::Mouse;
+        simulation.input(Input::Start {
+            pos: center + dir * 90.0,
+            cause,
+        });
+        simulation.input(Input::Finish {
+            pos: Some(center + dir * 110.0),
+            cause,
+        });
+        simulation
+    }
+
+    #[test]
+    fn cuts_behind_the_reversed_particle() {
+        // a quarter turn the other way, so the particle is on the top by now
+        let simulation = cut_behind_rule(-0.5, 1.0, 0.0);
+        let angle = simulation.particles()[0].angle;
+        assert!((angle + FRAC_PI_2).abs() < 1e-9);
+        assert_eq!(simulation.jiggles(), 0);
+        assert_eq!(cut_behind_rule(-0.5, 1.0, PI).jiggles(), 1);
+    }
+
+    #[test]
+    fn cuts_behind_the_particle_going_backwards() {
+        // the same as above, with the particle going the other way instead of the ring
+        let simulation = cut_behind_rule(0.5, -1.0, 0.0);
+        let angle = simulation.particles()[0].angle;
+        assert!((angle + FRAC_PI_2).abs() < 1e-9);
+        assert_eq!(simulation.jiggles(), 0);
+        assert_eq!(cut_behind_rule(0.5, -1.0, PI).jiggles(), 1);
+    }
+
+    #[test]
+    fn cuts_behind_the_backwards_particle_on_a_counter_rotating_ring() {
+        // both going backwards cancel out, so it is at the bottom with the cut behind it
+        let simulation = cut_behind_rule(-0.5, -1.0, 0.0);
+        let angle = simulation.particles()[0].angle;
+        assert!((angle - FRAC_PI_2).abs() < 1e-9);
+        assert_eq!(simulation.jiggles(), 0);
+        assert_eq!(cut_behind_rule(-0.5, -1.0, PI).jiggles(), 1);
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    f64::consts::TAU,
+};
+
+use nalgebra::Vector2;
+
+use crate::{
+    level::GameLevel,
+    simulation::{Disruption, DisruptionCause, Input, Simulation, Status},
+};
+
+// how many directions are tried to find a cut that crosses only one ring
+const CUT_DIRECTIONS: usize = 32;
+
+/// How good the imaginary player is, the cut itself costs
+/// `cut_length * cut_duration` on top of the base energy of the ring
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    pub size: Vector2<f64>,
+    pub cut_length: f64,
+    pub cut_duration: f64,
+    /// Time between the starts of two consecutive cuts
+    pub cut_interval: f64,
+    pub step: f64,
+    pub max_cuts: usize,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            size: [1280.0, 720.0].into(),
+            cut_length: 10.0,
+            cut_duration: 0.05,
+            cut_interval: 0.3,
+            step: 1.0 / 60.0,
+            max_cuts: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedCut {
+    /// A single ring, or all the rings of a link group
+    pub rings: Vec<usize>,
+    pub time: f64,
+    pub start: Vector2<f64>,
+    pub end: Vector2<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub cuts: Vec<PlannedCut>,
+    pub energy_spent: f64,
+    pub score: f64,
+}
+
+struct Node {
+    simulation: Simulation,
+    cuts: Vec<PlannedCut>,
+    spent: f64,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.spent == other.spent
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    // reversed, so that the binary heap pops the cheapest node first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .spent
+            .partial_cmp(&self.spent)
+            .expect("NaN not allowed")
+    }
+}
+
+/// A short radial cut across the given rings that does not touch any other ring
+/// or anything blocking it, and the fewest of the surcharged obstacles,
+/// with everything where it is at the given time, `allowed` checks the angle of the cut
+fn find_cut(
+    level: &GameLevel,
+    rings: &[usize],
+    time: f64,
+    config: &SolverConfig,
+    allowed: impl Fn(f64) -> bool,
+) -> Option<(Vector2<f64>, Vector2<f64>)> {
+    let center = config.size / 2.0;
+    let min_dim = config.size.min();
+    let offsets = level.ring_offsets(time);
+
+    (0..CUT_DIRECTIONS)
+        .map(|i| TAU * i as f64 / CUT_DIRECTIONS as f64)
+        .filter(|&angle| allowed(angle))
+        .map(|angle| {
+            let dir = Vector2::new(angle.cos(), angle.sin());
+            // from the innermost edge to the outermost one in that direction
+            let along = rings
+                .iter()
+                .map(|&ring| {
+                    let target = &level.rings[ring];
+                    let ring_center = center + offsets[ring] * min_dim;
+                    let edge = ring_center + target.shape.point_at(angle) * target.radius * min_dim;
+                    edge.dot(&dir)
+                })
+                .collect::<Vec<_>>();
+            let first = along.iter().copied().fold(f64::INFINITY, f64::min);
+            let last = along.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            // the edges are not always on one line, so the cut goes through the first of them
+            let ring_center = center + offsets[rings[0]] * min_dim;
+            let base = ring_center - dir * ring_center.dot(&dir);
+            let start = base + dir * (first - config.cut_length / 2.0);
+            let end = base + dir * (last + config.cut_length / 2.0);
+            (start, end)
+        })
+        .filter_map(|(start, end)| {
+            let disruption = Disruption {
+                start,
+                end,
+                start_time: 0.0,
+                cause: DisruptionCause::Mouse,
+            };
+            let hits_rings = level.rings.iter().enumerate().all(|(idx, r)| {
+                r.intersects(center, offsets[idx], &disruption) == rings.contains(&idx)
+            });
+            let crossed = level
+                .obstacles
+                .iter()
+                .filter(|o| o.crossed_by(center, time, &disruption))
+                .collect::<Vec<_>>();
+            if hits_rings && !crossed.iter().any(|o| o.blocks()) {
+                Some((crossed.len(), (start, end)))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|(crossed, _)| *crossed)
+        .map(|(_, cut)| cut)
+}
+
+fn advance(simulation: &mut Simulation, time: f64, step: f64) {
+    let target = simulation.time() + time;
+    while simulation.is_playing() && simulation.time() + step / 2.0 < target {
+        simulation.update(step);
+    }
+}
+
+// the particle angles do not affect the rules, so the nodes which
+// only differ in it (or in the time, unless the rings move) are considered the same
+fn state_key(simulation: &Simulation) -> Vec<i64> {
+    let mut key = simulation
+        .particles()
+        .iter()
+        .map(|p| p.ring.map_or(-1, |ring| ring as i64))
+        .collect::<Vec<_>>();
+    if simulation.level().is_moving() {
+        key.push((simulation.time() * 10.0).round() as i64);
+    }
+    key.extend(
+        simulation
+            .level()
+            .rings
+            .iter()
+            .map(|r| (r.disrupted_time.max(0.0) * 10.0).round() as i64),
+    );
+    key
+}
+
+/// Finds the cheapest sequence of cuts that frees the particle,
+/// following the same rules as the game itself does
+pub fn solve(level: &GameLevel, config: &SolverConfig) -> Option<Solution> {
+    let moving = level.is_moving();
+    // the linked rings can only be cut together with the rest of their group
+    let targets = (0..level.rings.len())
+        .filter(|&ring| level.link_group(ring).is_none())
+        .map(|ring| vec![ring])
+        .chain(level.links.iter().cloned())
+        .collect::<Vec<_>>();
+    let cuts = targets
+        .iter()
+        .map(|rings| find_cut(level, rings, 0.0, config, |_| true))
+        .collect::<Vec<_>>();
+
+    let mut visited = HashSet::new();
+    let mut queue = BinaryHeap::new();
+    queue.push(Node {
+        simulation: Simulation::new(level.clone(), config.size),
+        cuts: Vec::new(),
+        spent: 0.0,
+    });
+
+    while let Some(node) = queue.pop() {
+        if let Status::Won { score } = node.simulation.status() {
+            return Some(Solution {
+                cuts: node.cuts,
+                energy_spent: node.spent,
+                score,
+            });
+        }
+        if !node.simulation.is_playing()
+            || node.cuts.len() >= config.max_cuts
+            || !visited.insert(state_key(&node.simulation))
+        {
+            continue;
+        }
+
+        for (rings, cut) in targets.iter().zip(&cuts) {
+            let level_rings = &node.simulation.level().rings;
+            if rings
+                .iter()
+                .any(|&ring| level_rings[ring].disrupted_time > 1.0)
+            {
+                continue;
+            }
+
+            let mut simulation = node.simulation.clone();
+            let time = simulation.time();
+
+            let cut = if moving {
+                // aimed at where the ring is going to be when the cut is finished
+                let mut finished = simulation.clone();
+                advance(&mut finished, config.cut_duration, config.step);
+                let allowed = |angle| rings.iter().all(|&r| finished.cut_allowed(r, angle));
+                find_cut(level, rings, finished.time(), config, allowed)
+            } else {
+                *cut
+            };
+            let (start, end) = match cut {
+                Some(cut) => cut,
+                None => continue,
+            };
+
+            simulation.input(Input::Start {
+                pos: start,
+                cause: DisruptionCause::Mouse,
+            });
+            advance(&mut simulation, config.cut_duration, config.step);
+            let before = simulation.energy();
+            simulation.input(Input::Finish {
+                pos: Some(end),
+                cause: DisruptionCause::Mouse,
+            });
+            let spent = node.spent + before - simulation.energy();
+            advance(
+                &mut simulation,
+                config.cut_interval - config.cut_duration,
+                config.step,
+            );
+
+            let mut cuts = node.cuts.clone();
+            cuts.push(PlannedCut {
+                rings: rings.clone(),
+                time,
+                start,
+                end,
+            });
+            queue.push(Node {
+                simulation,
+                cuts,
+                spent,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::level;
+
+    fn cut_rings(solution: &Solution) -> Vec<Vec<usize>> {
+        solution.cuts.iter().map(|c| c.rings.clone()).collect()
+    }
+
+    #[test]
+    fn single_ring_takes_a_single_cut() {
+        let solution = solve(&level(30.0, &[(0.1, 10.0)]), &SolverConfig::default()).unwrap();
+        assert_eq!(cut_rings(&solution), vec![vec![0]]);
+        // the base energy, and a cut of 10 pixels held for 0.05 seconds
+        assert!((solution.energy_spent - 10.5).abs() < 1e-9);
+        assert!((solution.score - 97.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outer_ring_goes_first_when_it_has_more_energy() {
+        // the particle starts on the outer ring, cutting the inner one first would only jiggle it
+        let level = level(100.0, &[(0.1, 10.0), (0.2, 20.0)]);
+        let solution = solve(&level, &SolverConfig::default()).unwrap();
+        assert_eq!(cut_rings(&solution), vec![vec![1], vec![0]]);
+        assert!((solution.energy_spent - 31.0).abs() < 1e-9);
+        assert!((solution.score - (1.0 - 1.0 / 70.0) * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn linked_rings_are_cut_together() {
+        let mut level = level(100.0, &[(0.1, 10.0), (0.2, 5.0), (0.3, 1.0)]);
+        level.links = vec![vec![0, 1]];
+        let solution = solve(&level, &SolverConfig::default()).unwrap();
+        assert_eq!(cut_rings(&solution), vec![vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn every_particle_has_to_escape() {
+        let mut level: GameLevel = serde_json::from_value(json!({
+            "name": "a",
+            "energy": 100,
+            "rings": [
+                { "radius": 0.1, "base_energy": 10 },
+                { "radius": 0.2, "base_energy": 10 }
+            ],
+            "particles": [{ "ring": 0 }, { "ring": 1, "speed": 2 }]
+        }))
+        .unwrap();
+        // both rings have a particle on them, so neither of them only jiggles
+        let solution = solve(&level, &SolverConfig::default()).unwrap();
+        assert_eq!(cut_rings(&solution), vec![vec![0], vec![1]]);
+
+        // with the second one gone the first ring would only jiggle
+        level.particles.remove(0);
+        let solution = solve(&level, &SolverConfig::default()).unwrap();
+        assert_eq!(cut_rings(&solution), vec![vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn not_enough_energy_has_no_solution() {
+        let level = level(10.2, &[(0.1, 10.0)]);
+        assert!(solve(&level, &SolverConfig::default()).is_none());
+    }
+}
@@ -2,7 +2,7 @@ use crate::engine::ui::Button;
 use crate::states::level_select::LevelMenuState;
 use crate::{
     engine::{event::Event, Context, GameState, StateTransition},
-    states::main_game::MainGameState,
+    states::main_game::{self, MainGameState},
     QuantumLoops,
 };
 
@@ -11,6 +11,8 @@ pub struct GameLostState {
     game_state: MainGameState,
     level_menu: Button,
     retry: Button,
+    share: Button,
+    undo: Button,
 }
 
 impl GameLostState {
@@ -19,6 +21,8 @@ impl GameLostState {
             game_state,
             level_menu: Button::new("Level Menu".into()).with_size(1.5),
             retry: Button::new("Retry".into()),
+            share: Button::new("Share".into()).with_size(1.5),
+            undo: Button::new("Undo".into()),
         }
     }
 }
@@ -30,19 +34,35 @@ impl GameState<QuantumLoops> for GameLostState {
         context: &mut Context<QuantumLoops>,
     ) -> StateTransition<QuantumLoops> {
         if let Event::KeyDown { code: 82, .. } = event {
-            return StateTransition::set(MainGameState::new(self.game_state.level_idx()));
+            return StateTransition::set(self.game_state.restart());
+        }
+        if self.game_state.can_undo()
+            && (matches!(event, Event::KeyDown { code: 85, .. })
+                || self.undo.on_event(&event, context))
+        {
+            // the game goes on from before the last disruption, in place of this state
+            let restarted = self.game_state.restart();
+            let mut game_state = std::mem::replace(&mut self.game_state, restarted);
+            game_state.undo();
+            return StateTransition::set(game_state);
+        }
+        if let Some(daily) = self.game_state.daily_challenge() {
+            if self.share.on_event(&event, context) {
+                main_game::share_daily(context, daily.date);
+                return StateTransition::None;
+            }
         }
         if self.level_menu.on_event(&event, context) {
-            StateTransition::set(LevelMenuState::new())
+            StateTransition::set(LevelMenuState::for_game(&self.game_state))
         } else if self.retry.on_event(&event, context) {
-            StateTransition::set(MainGameState::new(self.game_state.level_idx()))
+            StateTransition::set(self.game_state.restart())
         } else {
             StateTransition::None
         }
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
-        self.game_state.on_update(context);
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        self.game_state.on_render(context);
 
         let surface = context.surface().context();
         surface.set_fill_style(&"red".into());
@@ -65,6 +85,17 @@ impl GameState<QuantumLoops> for GameLostState {
             context,
             [center.x, center.y + context.rem_to_px(3.5)].into(),
         );
+        if self.game_state.daily_challenge().is_some() {
+            self.share.on_update(
+                context,
+                [center.x, center.y + context.rem_to_px(5.5)].into(),
+            );
+        } else if self.game_state.can_undo() {
+            self.undo.on_update(
+                context,
+                [center.x, center.y + context.rem_to_px(5.5)].into(),
+            );
+        }
 
         StateTransition::None
     }
@@ -1,9 +1,9 @@
 use crate::{
     engine::{event::Event, ui::Button, Context, GameState, StateTransition},
-    level::StoredData,
+    level::{self, StoredData},
     states::{
         level_select::LevelMenuState,
-        main_game::{MainGameState, TEXT_COLOR},
+        main_game::{self, MainGameState, TEXT_COLOR},
         scores::ScoresState,
     },
     QuantumLoops,
@@ -15,8 +15,12 @@ pub struct GameWonState {
     next_level: Button,
     retry: Button,
     level_menu: Button,
+    replay: Button,
+    share: Button,
     score: f64,
     best: f64,
+    /// The completion time and the best one before it in the time attack mode
+    time: Option<(f64, Option<f64>)>,
 }
 
 impl GameWonState {
@@ -25,15 +29,58 @@ impl GameWonState {
             game_state,
             score,
             best: 0.0,
+            time: None,
             retry: Button::new("Retry".into()).with_size(1.5),
             level_menu: Button::new("Level Menu".into()).with_size(1.5),
+            replay: Button::new("Watch replay".into()).with_size(1.5),
+            share: Button::new("Share".into()).with_size(1.5),
             next_level: Button::empty(),
         }
     }
+
+    fn has_next_level(&self) -> bool {
+        self.game_state.is_builtin() || self.game_state.endless_run().is_some()
+    }
 }
 
 impl GameState<QuantumLoops> for GameWonState {
     fn on_pushed(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        // the time is kept on top of the score, which is stored as in the normal mode
+        if let Some(key) = self.game_state.best_time_key() {
+            let time = self.game_state.elapsed();
+            let mut storage = context.storage().clone();
+            self.time = Some((time, storage.best_times.get(&key).copied()));
+            if storage.record_time(&key, time) {
+                context.set_storage(storage);
+            }
+        }
+        if let Some(daily) = self.game_state.daily_challenge() {
+            if daily.scored {
+                let mut storage = context.storage().clone();
+                storage.daily.finish(daily.date, self.score);
+                context.set_storage(storage);
+            }
+            return StateTransition::None;
+        }
+        if self.game_state.endless_run().is_some() || !self.game_state.is_stored() {
+            return StateTransition::None;
+        }
+        if let Some(custom) = self.game_state.custom_level() {
+            let mut custom_scores = context.storage().custom_scores.clone();
+            self.best = custom_scores
+                .get(&custom.score_key)
+                .copied()
+                .unwrap_or_default();
+            if self.score > self.best {
+                custom_scores.insert(custom.score_key.clone(), self.score);
+                context.set_storage(StoredData {
+                    custom_scores,
+                    ..context.storage().clone()
+                });
+            }
+            return StateTransition::None;
+        }
+
         let mut best_scores = context.storage().best_scores.clone();
 
         let level_idx = self.game_state.level_idx();
@@ -45,8 +92,19 @@ impl GameState<QuantumLoops> for GameWonState {
             }
             best_scores[level_idx] = self.score;
 
+            let mut best_runs = context.storage().best_runs.clone();
+            while best_runs.len() <= level_idx {
+                best_runs.push(None);
+            }
+            best_runs[level_idx] = self
+                .game_state
+                .recording()
+                .filter(|r| r.fits_in_storage())
+                .cloned();
+
             context.set_storage(StoredData {
                 best_scores,
+                best_runs,
                 ..context.storage().clone()
             });
         }
@@ -58,26 +116,45 @@ impl GameState<QuantumLoops> for GameWonState {
         event: Event,
         context: &mut Context<QuantumLoops>,
     ) -> StateTransition<QuantumLoops> {
+        if let Some(daily) = self.game_state.daily_challenge() {
+            if self.share.on_event(&event, context) {
+                main_game::share_daily(context, daily.date);
+                return StateTransition::None;
+            }
+        }
         if let Event::KeyDown { code: 82, .. } = event {
-            StateTransition::set(MainGameState::new(self.game_state.level_idx()))
-        } else if self.next_level.on_event(&event, context) {
+            StateTransition::set(self.game_state.restart())
+        } else if self.has_next_level() && self.next_level.on_event(&event, context) {
+            if let Some(endless) = self.game_state.endless_run() {
+                return StateTransition::set(MainGameState::endless(
+                    endless.seed,
+                    endless.depth + 1,
+                ));
+            }
             let level_idx = self.game_state.level_idx();
             if level_idx == context.game.level_count() - 1 {
                 StateTransition::push(ScoresState::new())
             } else {
-                StateTransition::set(MainGameState::new(level_idx + 1))
+                StateTransition::set(
+                    MainGameState::new(level_idx + 1).with_play_mode(self.game_state.play_mode()),
+                )
             }
         } else if self.level_menu.on_event(&event, context) {
-            StateTransition::set(LevelMenuState::new())
+            StateTransition::set(LevelMenuState::for_game(&self.game_state))
         } else if self.retry.on_event(&event, context) {
-            StateTransition::set(MainGameState::new(self.game_state.level_idx()))
+            StateTransition::set(self.game_state.restart())
+        } else if self.replay.on_event(&event, context) {
+            match self.game_state.recording() {
+                Some(recording) => StateTransition::push(MainGameState::replay(recording.clone())),
+                None => StateTransition::None,
+            }
         } else {
             StateTransition::None
         }
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
-        self.game_state.on_update(context);
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        self.game_state.on_render(context);
 
         let center = context.surface().size() / 2.0;
         let surface = context.surface().context();
@@ -89,26 +166,74 @@ impl GameState<QuantumLoops> for GameWonState {
             .unwrap();
 
         surface.set_font("2rem monospace");
-        surface
-            .fill_text(
-                &format!("Efficiency: {:.2}%", self.score),
-                center.x,
-                center.y,
-            )
-            .unwrap();
+        if let Some((time, _)) = self.time {
+            surface
+                .fill_text(
+                    &format!("Time: {}", main_game::format_time(time)),
+                    center.x,
+                    center.y,
+                )
+                .unwrap();
+            surface
+                .fill_text(
+                    &format!("Efficiency: {:.2}%", self.score),
+                    center.x,
+                    center.y + context.rem_to_px(2.0),
+                )
+                .unwrap();
+        } else {
+            surface
+                .fill_text(
+                    &format!("Efficiency: {:.2}%", self.score),
+                    center.x,
+                    center.y,
+                )
+                .unwrap();
+        }
+
+        if let (Some(recording), None) = (self.game_state.recording(), self.time) {
+            let stars = recording.level.stars_for(self.score);
+            surface
+                .fill_text(
+                    &level::star_text(stars),
+                    center.x,
+                    center.y + context.rem_to_px(2.0),
+                )
+                .unwrap();
+        }
 
         surface.set_font("1rem monospace");
-        let subtext = if self.score > self.best {
+        let subtext = if let Some(endless) = self.game_state.endless_run() {
+            format!("{} levels into the run", endless.depth + 1)
+        } else if let Some(daily) = self.game_state.daily_challenge() {
+            if daily.scored {
+                let streak = context.storage().daily.current_streak(daily.date);
+                format!("daily streak: {}", streak)
+            } else {
+                "only the first attempt of the day is scored".into()
+            }
+        } else if !self.game_state.is_stored() {
+            "practice, the score is not saved".into()
+        } else if let Some((time, best)) = self.time {
+            match best {
+                Some(best) if best <= time => {
+                    format!("your best time is {}", main_game::format_time(best))
+                }
+                _ => "new best time!".into(),
+            }
+        } else if self.score > self.best {
             "new best!".into()
         } else {
             format!("your best is {:.2}%", self.best)
         };
         surface
-            .fill_text(&subtext, center.x, center.y + context.rem_to_px(1.5))
+            .fill_text(&subtext, center.x, center.y + context.rem_to_px(3.5))
             .unwrap();
 
         self.next_level.set_text(
-            if self.game_state.level_idx() != context.game.level_count() - 1 {
+            if self.game_state.endless_run().is_some()
+                || self.game_state.level_idx() != context.game.level_count() - 1
+            {
                 "Next Level"
             } else {
                 "View the scores"
@@ -118,16 +243,27 @@ impl GameState<QuantumLoops> for GameWonState {
 
         self.retry.on_update(
             context,
-            [center.x, center.y + context.rem_to_px(3.5)].into(),
+            [center.x, center.y + context.rem_to_px(5.5)].into(),
         );
         self.level_menu.on_update(
             context,
-            [center.x, center.y + context.rem_to_px(5.0)].into(),
+            [center.x, center.y + context.rem_to_px(7.0)].into(),
         );
-        self.next_level.on_update(
+        self.replay.on_update(
             context,
-            [center.x, center.y + context.rem_to_px(7.0)].into(),
+            [center.x, center.y + context.rem_to_px(8.5)].into(),
         );
+        if self.has_next_level() {
+            self.next_level.on_update(
+                context,
+                [center.x, center.y + context.rem_to_px(10.5)].into(),
+            );
+        } else if self.game_state.daily_challenge().is_some() {
+            self.share.on_update(
+                context,
+                [center.x, center.y + context.rem_to_px(10.5)].into(),
+            );
+        }
 
         StateTransition::None
     }
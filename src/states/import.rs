@@ -0,0 +1,114 @@
+use nalgebra::Vector2;
+
+use crate::{
+    engine::{event::Event, ui::Button, Context, GameState, StateTransition},
+    level::StoredData,
+    save_code::{self, SaveDiff},
+    states::{main_game::TEXT_COLOR, main_menu::Background},
+    QuantumLoops,
+};
+
+#[derive(Debug)]
+pub struct ImportState {
+    background: Background,
+    imported: StoredData,
+    diff: SaveDiff,
+    merge: Button,
+    replace: Button,
+    cancel: Button,
+}
+
+impl ImportState {
+    pub fn new(current: &StoredData, imported: StoredData) -> Self {
+        Self {
+            background: Background::new(),
+            diff: SaveDiff::new(current, &imported),
+            imported,
+            merge: Button::new("Merge".into()),
+            replace: Button::new("Replace".into()),
+            cancel: Button::new("Cancel".into()),
+        }
+    }
+
+    fn describe(&self, context: &Context<QuantumLoops>) -> Vec<String> {
+        let (current, imported) = self.diff.unlocked_level;
+        let mut lines = Vec::new();
+        if current != imported {
+            lines.push(format!(
+                "Unlocked levels: {} → {}",
+                current + 1,
+                imported + 1
+            ));
+        }
+        let levels = context.game.levels();
+        for change in &self.diff.scores {
+            let name = levels
+                .get(change.level_idx)
+                .map(|level| level.name.clone())
+                .unwrap_or_else(|| format!("Level {}", change.level_idx + 1));
+            lines.push(format!(
+                "{}: {:.2}% → {:.2}%",
+                name, change.current, change.imported
+            ));
+        }
+        if lines.is_empty() {
+            lines.push("Nothing would change".into());
+        }
+        lines
+    }
+}
+
+impl GameState<QuantumLoops> for ImportState {
+    fn on_event(
+        &mut self,
+        event: Event,
+        context: &mut Context<QuantumLoops>,
+    ) -> StateTransition<QuantumLoops> {
+        if let Event::KeyDown { code: 27, .. } = event {
+            return StateTransition::Pop;
+        }
+        if self.merge.on_event(&event, context) {
+            let merged = save_code::merge(context.storage(), &self.imported);
+            context.set_storage(merged);
+            StateTransition::Pop
+        } else if self.replace.on_event(&event, context) {
+            let replaced = save_code::replace(context.storage(), &self.imported);
+            context.set_storage(replaced);
+            StateTransition::Pop
+        } else if self.cancel.on_event(&event, context) {
+            StateTransition::Pop
+        } else {
+            StateTransition::None
+        }
+    }
+
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        let size = context.surface().size();
+        let x = size.x * 0.5;
+        let line = context.rem_to_px(1.5);
+        let offset: Vector2<f64> = [context.rem_to_px(10.0), 0.0].into();
+
+        self.background.on_update(context);
+
+        let lines = self.describe(context);
+        let mut y = size.y * 0.2;
+        {
+            let surface = context.surface().context();
+            surface.set_fill_style(&TEXT_COLOR.into());
+            surface.set_font("2.5rem monospace");
+            surface.fill_text("Import progress", x, y).unwrap();
+            surface.set_font("1.2rem monospace");
+            for text in &lines {
+                y += line;
+                surface.fill_text(text, x, y).unwrap();
+            }
+        }
+
+        let buttons: Vector2<f64> = [x, y + context.rem_to_px(4.0)].into();
+        self.merge.on_update(context, buttons - offset);
+        self.replace.on_update(context, buttons);
+        self.cancel.on_update(context, buttons + offset);
+
+        StateTransition::None
+    }
+}
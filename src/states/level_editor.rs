@@ -0,0 +1,560 @@
+use std::f64::consts::TAU;
+
+use nalgebra::Vector2;
+
+use crate::{
+    engine::{self, event::Event, ui::Button, Context, GameState, StateTransition},
+    level::{EnergyRing, GameLevel},
+    simulation::Input,
+    states::{
+        main_game::{
+            draw_background, draw_obstacles, ring_label, stroke_ring, MainGameState,
+            HOVERED_TEXT_COLOR, TEXT_COLOR,
+        },
+        main_menu::MainMenuState,
+    },
+    QuantumLoops,
+};
+
+const COLORS: [&str; 6] = ["black", "red", "green", "blue", "orange", "purple"];
+
+// how close (in pixels) the pointer has to be to grab a handle or a ring
+const GRAB_DISTANCE: f64 = 10.0;
+const MIN_RADIUS: f64 = 0.01;
+// in turns per second, either way
+const MAX_SPEED: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Energy,
+    Radius,
+    OffsetX,
+    OffsetY,
+    Width,
+    BaseEnergy,
+    RestoreTime,
+    Speed,
+}
+
+impl Field {
+    const ALL: [Field; 8] = [
+        Field::Energy,
+        Field::Radius,
+        Field::OffsetX,
+        Field::OffsetY,
+        Field::Width,
+        Field::BaseEnergy,
+        Field::RestoreTime,
+        Field::Speed,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Energy => "level energy",
+            Field::Radius => "radius",
+            Field::OffsetX => "offset x",
+            Field::OffsetY => "offset y",
+            Field::Width => "width",
+            Field::BaseEnergy => "base energy",
+            Field::RestoreTime => "restore time",
+            Field::Speed => "speed",
+        }
+    }
+
+    fn step(self) -> f64 {
+        match self {
+            Field::Energy => 5.0,
+            Field::Radius | Field::OffsetX | Field::OffsetY => 0.01,
+            Field::Width | Field::BaseEnergy => 1.0,
+            Field::RestoreTime | Field::Speed => 0.5,
+        }
+    }
+
+    fn min(self) -> f64 {
+        match self {
+            Field::Radius => MIN_RADIUS,
+            Field::OffsetX | Field::OffsetY => -1.0,
+            Field::Speed => -MAX_SPEED,
+            Field::Width => 1.0,
+            Field::Energy | Field::BaseEnergy | Field::RestoreTime => 0.0,
+        }
+    }
+
+    fn get(self, level: &GameLevel, selected: Option<usize>) -> Option<f64> {
+        if let Field::Energy = self {
+            return Some(level.energy);
+        }
+        let ring = &level.rings[selected?];
+        Some(match self {
+            Field::Energy => unreachable!(),
+            Field::Radius => ring.radius,
+            Field::OffsetX => ring.offset.x,
+            Field::OffsetY => ring.offset.y,
+            Field::Width => ring.width,
+            Field::BaseEnergy => ring.base_energy,
+            Field::RestoreTime => ring.restore_time,
+            Field::Speed => ring.speed,
+        })
+    }
+
+    fn set(self, level: &mut GameLevel, selected: Option<usize>, value: f64) {
+        let value = value.max(self.min());
+        if let Field::Energy = self {
+            level.energy = value;
+            return;
+        }
+        let ring = match selected {
+            Some(idx) => &mut level.rings[idx],
+            None => return,
+        };
+        match self {
+            Field::Energy => unreachable!(),
+            Field::Radius => ring.radius = value,
+            Field::OffsetX => ring.offset.x = value,
+            Field::OffsetY => ring.offset.y = value,
+            Field::Width => ring.width = value,
+            Field::BaseEnergy => ring.base_energy = value,
+            Field::RestoreTime => ring.restore_time = value,
+            Field::Speed => ring.speed = value.min(MAX_SPEED),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NumberField {
+    field: Field,
+    minus: Button,
+    value: Button,
+    plus: Button,
+}
+
+impl NumberField {
+    fn new(field: Field) -> Self {
+        Self {
+            field,
+            minus: Button::new(" - ".into()).with_size(1.2),
+            value: Button::empty().with_size(1.2),
+            plus: Button::new(" + ".into()).with_size(1.2),
+        }
+    }
+
+    fn buttons(&self) -> [&Button; 3] {
+        [&self.minus, &self.value, &self.plus]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Drag {
+    Create { ring: usize },
+    Move { ring: usize, grab: Vector2<f64> },
+    Resize { ring: usize },
+}
+
+#[derive(Debug)]
+pub struct LevelEditorState {
+    level: GameLevel,
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    back: Button,
+    name: Button,
+    playtest: Button,
+    import: Button,
+    export: Button,
+    color: Button,
+    shape: Button,
+    delete: Button,
+    fields: Vec<NumberField>,
+}
+
+impl LevelEditorState {
+    pub fn new(level: GameLevel) -> Self {
+        Self {
+            level,
+            selected: None,
+            drag: None,
+            back: Button::new(" ← back  ".into()).with_size(1.5),
+            name: Button::empty().with_size(1.5),
+            playtest: Button::new("Playtest".into()).with_size(1.5),
+            import: Button::new("Import".into()).with_size(1.5),
+            export: Button::new("Export".into()).with_size(1.5),
+            color: Button::empty().with_size(1.2),
+            shape: Button::empty().with_size(1.2),
+            delete: Button::new("delete ring".into()).with_size(1.2),
+            fields: Field::ALL.iter().copied().map(NumberField::new).collect(),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(GameLevel::new("New level".into(), 100.0, Vec::new()))
+    }
+
+    fn over_ui(&self, pos: Vector2<f64>, context: &mut Context<QuantumLoops>) -> bool {
+        let mut buttons = vec![
+            &self.back,
+            &self.name,
+            &self.playtest,
+            &self.import,
+            &self.export,
+        ];
+        if self.selected.is_some() {
+            buttons.push(&self.color);
+            buttons.push(&self.shape);
+            buttons.push(&self.delete);
+        }
+        // the fields of the ring are not shown when nothing is selected
+        buttons.extend(
+            self.fields
+                .iter()
+                .filter(|field| field.field.get(&self.level, self.selected).is_some())
+                .flat_map(NumberField::buttons),
+        );
+        buttons
+            .iter()
+            .any(|button| button.text.is_over(pos, context))
+    }
+
+    fn on_button_event(&mut self, event: &Event, context: &mut Context<QuantumLoops>) -> bool {
+        let window = engine::window();
+        if self.name.on_event(event, context) {
+            if let Ok(Some(name)) =
+                window.prompt_with_message_and_default("Level name", &self.level.name)
+            {
+                self.level.name = name;
+            }
+        } else if self.import.on_event(event, context) {
+            if let Ok(Some(json)) = window.prompt_with_message("Paste the level JSON") {
+                match serde_json::from_str(&json) {
+                    Ok(level) => {
+                        self.level = level;
+                        self.selected = None;
+                    }
+                    Err(e) => {
+                        let _ = window.alert_with_message(&format!("Invalid level: {}", e));
+                    }
+                }
+            }
+        } else if self.export.on_event(event, context) {
+            let json = serde_json::to_string_pretty(&self.level).unwrap();
+            let file_name = format!("{}.json", self.level.name);
+            if let Err(e) = engine::download(&file_name, &json) {
+                log::error!("Failed to download the level: {:?}", e);
+            }
+            let location = window.location();
+            let link = format!(
+                "{}{}#level={}",
+                location.origin().unwrap_or_default(),
+                location.pathname().unwrap_or_default(),
+                self.level.to_share_code()
+            );
+            let _ = window.prompt_with_message_and_default("Link to share the level", &link);
+        } else if self.color.on_event(event, context) {
+            if let Some(idx) = self.selected {
+                let ring = &mut self.level.rings[idx];
+                if let Ok(Some(color)) =
+                    window.prompt_with_message_and_default("Ring color", &ring.color)
+                {
+                    ring.color = color;
+                }
+            }
+        } else if self.shape.on_event(event, context) {
+            if let Some(idx) = self.selected {
+                let ring = &mut self.level.rings[idx];
+                let json = serde_json::to_string(&ring.shape).unwrap();
+                if let Ok(Some(json)) =
+                    window.prompt_with_message_and_default("Ring shape (JSON)", &json)
+                {
+                    match serde_json::from_str(&json) {
+                        Ok(shape) => ring.shape = shape,
+                        Err(e) => {
+                            let _ = window.alert_with_message(&format!("Invalid shape: {}", e));
+                        }
+                    }
+                }
+            }
+        } else if self.delete.on_event(event, context) {
+            if let Some(idx) = self.selected.take() {
+                self.level.remove_ring(idx);
+            }
+        } else {
+            let (level, selected) = (&mut self.level, self.selected);
+            for field in &mut self.fields {
+                let value = match field.field.get(level, selected) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let step = field.field.step();
+                if field.minus.on_event(event, context) {
+                    field.field.set(level, selected, value - step);
+                } else if field.plus.on_event(event, context) {
+                    field.field.set(level, selected, value + step);
+                } else if field.value.on_event(event, context) {
+                    let entered = window
+                        .prompt_with_message_and_default(field.field.label(), &value.to_string());
+                    if let Ok(Some(entered)) = entered {
+                        match entered.trim().parse() {
+                            Ok(value) => field.field.set(level, selected, value),
+                            Err(_) => log::warn!("'{}' is not a number", entered),
+                        }
+                    }
+                } else {
+                    continue;
+                }
+                return true;
+            }
+            return false;
+        }
+        true
+    }
+
+    fn ring_at(&self, pos: Vector2<f64>, center: Vector2<f64>, min_dim: f64) -> Option<usize> {
+        // the innermost ring wins when they are close to each other
+        self.level
+            .rings
+            .iter()
+            .enumerate()
+            .filter(|(_, ring)| {
+                let from_center = pos - (center + ring.offset * min_dim);
+                let angle = from_center.y.atan2(from_center.x);
+                let edge = ring.shape.point_at(angle).norm() * ring.radius * min_dim;
+                (from_center.norm() - edge).abs() <= GRAB_DISTANCE.max(ring.width)
+            })
+            .min_by(|(_, r1), (_, r2)| r1.radius.partial_cmp(&r2.radius).expect("NaN not allowed"))
+            .map(|(idx, _)| idx)
+    }
+
+    fn start_drag(&mut self, pos: Vector2<f64>, center: Vector2<f64>, min_dim: f64) {
+        if let Some(idx) = self.selected {
+            let ring = &self.level.rings[idx];
+            let ring_center = center + ring.offset * min_dim;
+            if pos.metric_distance(&resize_handle(ring, ring_center, min_dim)) <= GRAB_DISTANCE {
+                self.drag = Some(Drag::Resize { ring: idx });
+                return;
+            }
+            if pos.metric_distance(&ring_center) <= GRAB_DISTANCE {
+                self.drag = Some(Drag::Move {
+                    ring: idx,
+                    grab: ring_center - pos,
+                });
+                return;
+            }
+        }
+        if let Some(idx) = self.ring_at(pos, center, min_dim) {
+            let ring_center = center + self.level.rings[idx].offset * min_dim;
+            self.selected = Some(idx);
+            self.drag = Some(Drag::Move {
+                ring: idx,
+                grab: ring_center - pos,
+            });
+            return;
+        }
+        let color = COLORS[self.level.rings.len() % COLORS.len()];
+        self.level.rings.push(EnergyRing {
+            color: color.into(),
+            ..EnergyRing::new((pos - center) / min_dim, 0.0, 10.0)
+        });
+        let idx = self.level.rings.len() - 1;
+        self.selected = Some(idx);
+        self.drag = Some(Drag::Create { ring: idx });
+    }
+
+    fn update_drag(&mut self, pos: Vector2<f64>, center: Vector2<f64>, min_dim: f64) {
+        match self.drag {
+            Some(Drag::Create { ring }) | Some(Drag::Resize { ring }) => {
+                let ring = &mut self.level.rings[ring];
+                let ring_center = center + ring.offset * min_dim;
+                // the handle stays on the outline, which is not at the radius for every shape
+                let edge = ring.shape.point_at(0.0).norm();
+                ring.radius = pos.metric_distance(&ring_center) / min_dim / edge;
+            }
+            Some(Drag::Move { ring, grab }) => {
+                self.level.rings[ring].offset = (pos + grab - center) / min_dim;
+            }
+            None => {}
+        }
+    }
+
+    fn finish_drag(&mut self) {
+        if let Some(Drag::Create { ring }) | Some(Drag::Resize { ring }) = self.drag.take() {
+            // a click on an empty spot only deselects, it does not leave a dot behind
+            if self.level.rings[ring].radius < MIN_RADIUS {
+                self.level.remove_ring(ring);
+                self.selected = None;
+            }
+        }
+    }
+}
+
+fn resize_handle(ring: &EnergyRing, ring_center: Vector2<f64>, min_dim: f64) -> Vector2<f64> {
+    ring_center + ring.shape.point_at(0.0) * ring.radius * min_dim
+}
+
+fn format_value(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+impl GameState<QuantumLoops> for LevelEditorState {
+    fn on_event(
+        &mut self,
+        event: Event,
+        context: &mut Context<QuantumLoops>,
+    ) -> StateTransition<QuantumLoops> {
+        match event {
+            Event::KeyDown { code: 27, .. } => return StateTransition::set(MainMenuState::new()),
+            // delete and backspace
+            Event::KeyDown { code: 46, .. } | Event::KeyDown { code: 8, .. } => {
+                if let Some(idx) = self.selected.take() {
+                    self.level.remove_ring(idx);
+                }
+                return StateTransition::None;
+            }
+            _ => {}
+        }
+        if self.back.on_event(&event, context) {
+            return StateTransition::set(MainMenuState::new());
+        }
+        if self.playtest.on_event(&event, context) {
+            if self.level.rings.is_empty() {
+                return StateTransition::None;
+            }
+            return StateTransition::push(MainGameState::playtest(self.level.clone()));
+        }
+        if self.on_button_event(&event, context) {
+            return StateTransition::None;
+        }
+
+        let center = context.surface().size() / 2.0;
+        let min_dim = center.min() * 2.0;
+        match Input::from_event(&event) {
+            Some(Input::Start { pos, .. }) if !self.over_ui(pos, context) => {
+                self.start_drag(pos, center, min_dim)
+            }
+            Some(Input::Move {
+                pos,
+                dragging: true,
+                ..
+            }) => self.update_drag(pos, center, min_dim),
+            Some(Input::Finish { pos, .. }) => {
+                if let Some(pos) = pos {
+                    self.update_drag(pos, center, min_dim);
+                }
+                self.finish_drag();
+            }
+            _ => {}
+        }
+        StateTransition::None
+    }
+
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        let size = context.surface().size();
+        let center = size / 2.0;
+        let min_dim = size.min();
+
+        draw_background(context, center);
+
+        {
+            let surface = context.surface().context();
+            draw_obstacles(&surface, &self.level.obstacles, center, min_dim, 0.0);
+            for (idx, ring) in self.level.rings.iter().enumerate() {
+                let pos = center + ring.offset * min_dim;
+                let radius = ring.radius * min_dim;
+
+                surface.set_stroke_style(&(&ring.color).into());
+                surface.set_line_width(ring.width);
+                stroke_ring(&surface, ring, pos, radius);
+
+                surface.set_fill_style(&TEXT_COLOR.into());
+                surface.set_font("0.9rem monospace");
+                surface
+                    .fill_text(
+                        &ring_label(&self.level, idx),
+                        pos.x,
+                        pos.y - radius * ring.shape.extent().y - 8.0,
+                    )
+                    .unwrap();
+
+                if Some(idx) == self.selected {
+                    let handle = resize_handle(ring, pos, min_dim);
+                    surface.set_fill_style(&HOVERED_TEXT_COLOR.into());
+                    for p in &[pos, handle] {
+                        surface.begin_path();
+                        surface
+                            .arc(p.x, p.y, GRAB_DISTANCE / 2.0, 0.0, TAU)
+                            .unwrap();
+                        surface.fill();
+                    }
+                }
+            }
+        }
+
+        let rem = context.rem_to_px(1.0);
+
+        // the toolbar on the top
+        let name = format!("name: {}", self.level.name);
+        self.name.set_text(name.into());
+        let mut toolbar = [
+            &mut self.back,
+            &mut self.name,
+            &mut self.playtest,
+            &mut self.import,
+            &mut self.export,
+        ];
+        let y = rem * 2.0;
+        let step = size.x / (toolbar.len() + 1) as f64;
+        for (i, button) in toolbar.iter_mut().enumerate() {
+            button.on_update(context, [step * (i + 1) as f64, y].into());
+        }
+
+        // the fields on the left
+        let free = self.level.energy
+            - self
+                .level
+                .rings
+                .iter()
+                .map(|ring| ring.base_energy)
+                .sum::<f64>();
+        let x = rem * 9.0;
+        let mut y = rem * 6.0;
+        {
+            let surface = context.surface().context();
+            surface.set_fill_style(&TEXT_COLOR.into());
+            surface.set_font("1rem monospace");
+            surface
+                .fill_text(&format!("free energy: {:.2}", free), x, y)
+                .unwrap();
+        }
+
+        for field in &mut self.fields {
+            let value = match field.field.get(&self.level, self.selected) {
+                Some(value) => value,
+                None => continue,
+            };
+            y += rem * 2.5;
+            {
+                let surface = context.surface().context();
+                surface.set_fill_style(&TEXT_COLOR.into());
+                surface.set_font("1rem monospace");
+                surface.fill_text(field.field.label(), x, y - rem).unwrap();
+            }
+            field.value.set_text(format_value(value).into());
+            field.minus.on_update(context, [x - rem * 5.0, y].into());
+            field.value.on_update(context, [x, y].into());
+            field.plus.on_update(context, [x + rem * 5.0, y].into());
+        }
+
+        if let Some(idx) = self.selected {
+            let color = format!("color: {}", self.level.rings[idx].color);
+            self.color.set_text(color.into());
+            y += rem * 2.5;
+            self.color.on_update(context, [x, y].into());
+            let shape = format!("shape: {}", self.level.rings[idx].shape.name());
+            self.shape.set_text(shape.into());
+            y += rem * 2.0;
+            self.shape.on_update(context, [x, y].into());
+            y += rem * 2.0;
+            self.delete.on_update(context, [x, y].into());
+        }
+
+        StateTransition::None
+    }
+}
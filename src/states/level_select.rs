@@ -1,18 +1,139 @@
 use crate::{
-    engine::{event::Event, ui::Button, Context, GameState, StateTransition},
+    engine::{self, event::Event, ui::Button, Context, GameState, StateTransition},
+    generator,
+    level::{self, StoredData},
+    pack::{self, LevelPack, UnlockRule},
     states::{
-        main_game::MainGameState,
+        main_game::{self, CustomLevel, MainGameState, PlayMode, TEXT_COLOR},
         main_menu::{Background, MainMenuState},
     },
     QuantumLoops,
 };
 use nalgebra::Vector2;
 
+const PASTED_PACK_KEY: &str = "pasted";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackId {
+    /// The pack from the levels file, its progress is stored by the level index
+    Main,
+    Custom(String),
+}
+
+#[derive(Debug)]
+enum Target {
+    Back,
+    Pack(PackId),
+    Level(usize),
+    Custom(Box<CustomLevel>),
+    Endless,
+    AddCustom,
+    PlayMode,
+}
+
+#[derive(Debug)]
+enum Entry {
+    Button(Button, Target),
+    Header(String),
+    Info(String),
+}
+
+// asks for the JSON of one or more levels and keeps them in the storage
+fn add_custom_levels(context: &mut Context<QuantumLoops>) {
+    let window = engine::window();
+    let json = match window.prompt_with_message("Paste the JSON of a level or a list of levels") {
+        Ok(Some(json)) if !json.trim().is_empty() => json,
+        _ => return,
+    };
+    let levels = match level::parse_levels(&json) {
+        Ok(levels) => levels,
+        Err(e) => {
+            let _ = window.alert_with_message(&format!("Invalid levels: {}", e));
+            return;
+        }
+    };
+    let mut custom_levels = context.storage().custom_levels.clone();
+    for level in levels {
+        // pasting a level again replaces the old version of it
+        custom_levels.retain(|l| l.name != level.name);
+        custom_levels.push(level);
+    }
+    context.set_storage(StoredData {
+        custom_levels,
+        ..context.storage().clone()
+    });
+}
+
+fn packs(context: &Context<QuantumLoops>) -> Vec<(PackId, LevelPack)> {
+    let mut packs = Vec::new();
+    if let Some(pack) = context.game.main_pack() {
+        packs.push((PackId::Main, pack));
+    }
+    for custom in context.game.custom_packs() {
+        packs.push((PackId::Custom(custom.key), custom.pack));
+    }
+    let pasted = &context.storage().custom_levels;
+    if !pasted.is_empty() {
+        let pack = LevelPack {
+            unlock: UnlockRule::AllOpen,
+            ..LevelPack::new("Pasted levels".into(), pasted.clone())
+        };
+        packs.push((PackId::Custom(PASTED_PACK_KEY.into()), pack));
+    }
+    packs
+}
+
+// the best score of every level, `None` for the ones that were never won
+fn pack_scores(id: &PackId, pack: &LevelPack, storage: &StoredData) -> Vec<Option<f64>> {
+    pack.levels
+        .iter()
+        .enumerate()
+        .map(|(idx, level)| match id {
+            PackId::Main => storage.best_scores.get(idx).copied().filter(|s| *s > 0.0),
+            PackId::Custom(key) => storage
+                .custom_scores
+                .get(&level::score_key(key, level))
+                .copied(),
+        })
+        .collect()
+}
+
+// the best time of every level in the time attack mode, `None` for the ones never won in it
+fn pack_times(id: &PackId, pack: &LevelPack, storage: &StoredData) -> Vec<Option<f64>> {
+    pack.levels
+        .iter()
+        .map(|level| {
+            let key = match id {
+                PackId::Main => level.name.clone(),
+                PackId::Custom(key) => level::score_key(key, level),
+            };
+            storage.best_times.get(&key).copied()
+        })
+        .collect()
+}
+
+fn pack_unlocked(id: &PackId, pack: &LevelPack, storage: &StoredData) -> Vec<bool> {
+    match (id, &pack.unlock) {
+        // the main pack always stored how far the player got, so that is kept
+        (PackId::Main, UnlockRule::Linear) => (0..pack.levels.len())
+            .map(|idx| idx <= storage.unlocked_level)
+            .collect(),
+        _ => pack
+            .unlock
+            .unlocked(&pack.levels, &pack_scores(id, pack, storage)),
+    }
+}
+
 #[derive(Debug)]
 pub struct LevelMenuState {
-    levels_added: bool,
+    /// The pack whose levels are shown, the pack chooser is shown when none
+    pack: Option<PackId>,
+    /// How the levels are started
+    play_mode: PlayMode,
+    // the levels arrive asynchronously, so the entries are rebuilt when their counts change
+    built_for: Option<(Option<PackId>, usize, usize, usize)>,
     background: Background,
-    buttons: Vec<Button>,
+    entries: Vec<Entry>,
     scroll: f64,
     button_limit: f64,
     last_touch: Option<Vector2<f64>>,
@@ -21,14 +142,142 @@ pub struct LevelMenuState {
 impl LevelMenuState {
     pub fn new() -> Self {
         Self {
-            levels_added: false,
+            pack: None,
+            play_mode: PlayMode::Normal,
+            built_for: None,
             background: Background::new(),
-            buttons: Vec::new(),
+            entries: Vec::new(),
             scroll: 0.0,
             button_limit: 0.0,
             last_touch: None,
         }
     }
+
+    pub fn with_pack(pack: PackId) -> Self {
+        Self {
+            pack: Some(pack),
+            ..Self::new()
+        }
+    }
+
+    /// Opens the pack that the level of the game is from
+    pub fn for_game(game_state: &MainGameState) -> Self {
+        if game_state.endless_run().is_some() || game_state.daily_challenge().is_some() {
+            return Self::new();
+        }
+        Self {
+            play_mode: game_state.play_mode(),
+            ..Self::with_pack(match game_state.custom_level() {
+                Some(custom) => PackId::Custom(custom.pack_key.clone()),
+                None => PackId::Main,
+            })
+        }
+    }
+
+    fn open(&mut self, pack: Option<PackId>) {
+        self.pack = pack;
+        self.built_for = None;
+        self.scroll = 0.0;
+    }
+
+    fn build_chooser(&mut self, context: &Context<QuantumLoops>) {
+        self.entries
+            .push(Entry::Button(Button::new(" ← back  ".into()), Target::Back));
+        let storage = context.storage();
+        let packs = packs(context)
+            .into_iter()
+            .map(|(id, pack)| {
+                let scores = pack_scores(&id, &pack, storage);
+                let stars = pack::total_stars(&pack.levels, &scores);
+                (id, pack, scores, stars)
+            })
+            .collect::<Vec<_>>();
+        let total_stars = packs.iter().map(|(.., stars)| stars).sum::<u32>();
+
+        for (id, pack, scores, stars) in packs {
+            let won = scores.iter().filter(|s| s.is_some()).count();
+            let unlocked = total_stars >= pack.required_stars;
+            let mut info = if unlocked {
+                format!("{}/{} won, {} stars", won, pack.levels.len(), stars)
+            } else {
+                format!(
+                    "needs {} stars, you have {}",
+                    pack.required_stars, total_stars
+                )
+            };
+            if let Some(author) = &pack.author {
+                info = format!("by {}, {}", author, info);
+            }
+            let mut button = Button::new(pack.title.into());
+            button.enabled = unlocked;
+            self.entries.push(Entry::Button(button, Target::Pack(id)));
+            self.entries.push(Entry::Info(info));
+        }
+        let (year, month, day) = engine::today();
+        self.entries.push(Entry::Button(
+            Button::new("Endless".into()),
+            Target::Endless,
+        ));
+        self.entries.push(Entry::Info(format!(
+            "generated levels, seed of {}-{:02}-{:02}",
+            year, month, day
+        )));
+        self.entries.push(Entry::Button(
+            Button::new("+ add custom levels".into()).with_size(1.5),
+            Target::AddCustom,
+        ));
+    }
+
+    fn build_levels(&mut self, id: &PackId, context: &Context<QuantumLoops>) {
+        self.entries
+            .push(Entry::Button(Button::new(" ← back  ".into()), Target::Back));
+        let pack = match packs(context)
+            .into_iter()
+            .find(|(pack_id, _)| pack_id == id)
+        {
+            Some((_, pack)) => pack,
+            // not loaded yet
+            None => return,
+        };
+        self.entries.push(Entry::Header(pack.title.clone()));
+        if let Some(description) = &pack.description {
+            self.entries.push(Entry::Info(description.clone()));
+        }
+
+        self.entries.push(Entry::Button(
+            Button::new(format!("mode: {}", self.play_mode.name()).into()).with_size(1.5),
+            Target::PlayMode,
+        ));
+
+        let storage = context.storage();
+        let scores = pack_scores(id, &pack, storage);
+        let times = pack_times(id, &pack, storage);
+        let unlocked = pack_unlocked(id, &pack, storage);
+
+        for (idx, level) in pack.levels.iter().enumerate() {
+            let text = match (self.play_mode, scores[idx], times[idx]) {
+                (PlayMode::TimeAttack, _, Some(time)) => {
+                    format!("{} {}", level.name, main_game::format_time(time))
+                }
+                (PlayMode::TimeAttack, ..) => level.name.clone(),
+                (_, Some(best), _) => {
+                    format!("{} {}", level.name, level::star_text(level.stars_for(best)))
+                }
+                _ => level.name.clone(),
+            };
+            let target = match id {
+                PackId::Main => Target::Level(idx),
+                PackId::Custom(key) => Target::Custom(Box::new(CustomLevel {
+                    level: level.clone(),
+                    pack_key: key.clone(),
+                    score_key: level::score_key(key, level),
+                })),
+            };
+            let mut button = Button::new(text.into());
+            button.enabled = unlocked[idx];
+            self.entries.push(Entry::Button(button, target));
+        }
+    }
 }
 
 impl GameState<QuantumLoops> for LevelMenuState {
@@ -38,7 +287,13 @@ impl GameState<QuantumLoops> for LevelMenuState {
         context: &mut Context<QuantumLoops>,
     ) -> StateTransition<QuantumLoops> {
         match &event {
-            Event::KeyDown { code: 27, .. } => return StateTransition::set(MainMenuState::new()),
+            Event::KeyDown { code: 27, .. } => {
+                if self.pack.is_some() {
+                    self.open(None);
+                    return StateTransition::None;
+                }
+                return StateTransition::set(MainMenuState::new());
+            }
             Event::MouseWheel { delta, .. } => {
                 let new_scroll = self.scroll + delta.y * 10.0;
                 if new_scroll >= 0.0 && new_scroll < self.button_limit {
@@ -70,48 +325,92 @@ impl GameState<QuantumLoops> for LevelMenuState {
             }
             _ => {}
         }
-        for (i, button) in self.buttons.iter_mut().enumerate() {
-            if button.on_event(&event, context) {
-                return StateTransition::Set(if i == 0 {
-                    Box::new(MainMenuState::new())
-                } else {
-                    Box::new(MainGameState::new(i - 1))
-                });
+        for entry in &mut self.entries {
+            if let Entry::Button(button, target) = entry {
+                if button.on_event(&event, context) {
+                    return match target {
+                        Target::Back if self.pack.is_some() => {
+                            self.open(None);
+                            StateTransition::None
+                        }
+                        Target::Back => StateTransition::set(MainMenuState::new()),
+                        Target::Pack(id) => {
+                            let id = id.clone();
+                            self.open(Some(id));
+                            StateTransition::None
+                        }
+                        Target::Level(idx) => StateTransition::set(
+                            MainGameState::new(*idx).with_play_mode(self.play_mode),
+                        ),
+                        Target::Custom(custom) => StateTransition::set(
+                            MainGameState::custom(*custom.clone()).with_play_mode(self.play_mode),
+                        ),
+                        Target::Endless => {
+                            let (year, month, day) = engine::today();
+                            let seed = generator::daily_seed(year, month, day);
+                            StateTransition::set(MainGameState::endless(seed, 0))
+                        }
+                        Target::AddCustom => {
+                            add_custom_levels(context);
+                            self.built_for = None;
+                            StateTransition::None
+                        }
+                        Target::PlayMode => {
+                            self.play_mode = self.play_mode.next();
+                            self.built_for = None;
+                            StateTransition::None
+                        }
+                    };
+                }
             }
         }
         StateTransition::None
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
         self.background.on_update(context);
 
         let size = context.surface().size();
         let off = context.rem_to_px(2.5) * 1.5;
 
-        // levels finally arrived
-        if let Some(levels) = context.game.levels.borrow_mut().as_ref() {
-            if !self.levels_added {
-                self.levels_added = true;
-                self.buttons.push(Button::new(" ← back  ".into()));
-
-                let unlocked = context.storage().unlocked_level;
-
-                for (idx, level) in levels.iter().enumerate() {
-                    let mut button = Button::new(level.name.clone().into());
-                    button.enabled = idx <= unlocked;
-                    self.buttons.push(button);
-                }
-
-                self.button_limit = self.buttons.len() as f64 * off - size.y * 0.25;
+        let counts = (
+            self.pack.clone(),
+            context.game.level_count(),
+            context.game.custom_packs.borrow().len(),
+            context.storage().custom_levels.len(),
+        );
+        if self.built_for.as_ref() != Some(&counts) {
+            self.built_for = Some(counts);
+            self.entries.clear();
+            match self.pack.clone() {
+                Some(id) => self.build_levels(&id, context),
+                None => self.build_chooser(context),
             }
+            self.button_limit = self.entries.len() as f64 * off - size.y * 0.25;
         }
 
         let x = size.x * 0.5;
         let mut y = size.y * 0.25 - self.scroll;
 
-        for button in &mut self.buttons {
+        for entry in &mut self.entries {
             y += off;
-            button.on_update(context, [x, y].into());
+            match entry {
+                Entry::Button(button, _) => button.on_update(context, [x, y].into()),
+                Entry::Header(text) => {
+                    let surface = context.surface().context();
+                    surface.set_fill_style(&TEXT_COLOR.into());
+                    surface.set_font("3rem monospace");
+                    surface.fill_text(text, x, y).unwrap();
+                }
+                Entry::Info(text) => {
+                    // belongs to the entry above, so it is drawn closer to it
+                    y -= off / 2.0;
+                    let surface = context.surface().context();
+                    surface.set_fill_style(&TEXT_COLOR.into());
+                    surface.set_font("1rem monospace");
+                    surface.fill_text(text, x, y).unwrap();
+                }
+            }
         }
 
         StateTransition::None
@@ -2,20 +2,21 @@ use nalgebra::Vector2;
 use noise::{NoiseFn, Perlin};
 
 use crate::{
-    engine::{
-        self,
-        event::{Event, MouseButton},
-        util::SmoothChange,
-        Context, GameState, StateTransition,
-    },
-    level::GameLevel,
-    level::StoredData,
+    daily::{self, Date},
+    engine::{self, event::Event, util::SmoothChange, Context, GameState, StateTransition},
+    generator,
+    level::{CutRule, EnergyRing, GameLevel, StoredData},
+    obstacle::{Obstacle, ObstacleKind},
+    recording::{Frame, Recording},
+    shape::Shape,
+    simulation::{Disruption, Input, Simulation, Status, JIGGLE_TIME},
     states::game_lost::GameLostState,
     states::game_won::GameWonState,
     states::pause::PauseState,
     QuantumLoops,
 };
 use std::f64::consts::{FRAC_PI_4, TAU};
+use web_sys::CanvasRenderingContext2d;
 
 pub const BG_COLOR: &str = "#ebf2f5";
 pub const BG_LINE_COLOR: &str = "#d2e0fa";
@@ -23,22 +24,8 @@ pub const TEXT_COLOR: &str = "#119ad9";
 pub const DISABLED_TEXT_COLOR: &str = "#77868c";
 pub const HOVERED_TEXT_COLOR: &str = "#0a5a80";
 pub const ENERGY_BAR_COLOR: &str = "#93d6f5";
-
-pub const JIGGLE_TIME: f64 = 0.25;
-
-#[derive(Debug)]
-enum DisruptionCause {
-    Mouse,
-    Touch,
-}
-
-#[derive(Debug)]
-pub struct Disruption {
-    pub start: Vector2<f64>,
-    pub end: Vector2<f64>,
-    pub start_time: f64,
-    cause: DisruptionCause,
-}
+pub const SHIELD_COLOR: &str = "#3d4a52";
+pub const NO_CUT_COLOR: &str = "#e0564f";
 
 #[derive(Debug)]
 enum GameStatus {
@@ -48,18 +35,113 @@ enum GameStatus {
     Lost,
 }
 
+/// How the levels picked in the level menu are played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Normal,
+    /// Against the clock, the best times are kept on top of the scores
+    TimeAttack,
+    /// The disruptions can be undone, nothing is stored about the attempts
+    Practice,
+}
+
+impl PlayMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            PlayMode::Normal => "normal",
+            PlayMode::TimeAttack => "time attack",
+            PlayMode::Practice => "practice",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            PlayMode::Normal => PlayMode::TimeAttack,
+            PlayMode::TimeAttack => PlayMode::Practice,
+            PlayMode::Practice => PlayMode::Normal,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MainGameState {
-    level: Option<GameLevel>,
     level_idx: usize,
-    current_ring: usize,
-    particle_angle: f64,
+    simulation: Option<Simulation>,
+    recording: Option<Recording>,
+    frame: Frame,
+    mode: Mode,
+    play_mode: PlayMode,
+    prev_particle_angles: Vec<f64>,
     game_status: GameStatus,
     energy: SmoothChange,
-    disruption: Option<Disruption>,
     noise: Perlin,
 }
 
+/// Where the level comes from, and so what is kept about it
+#[derive(Debug)]
+enum Mode {
+    /// The level of the levels file at the index
+    Campaign,
+    Playtest(Playtest),
+    Custom(CustomLevel),
+    Endless(Endless),
+    Daily(Daily),
+    Replay(Replay),
+}
+
+#[derive(Debug)]
+struct Replay {
+    next_frame: usize,
+    end_timer: f64,
+}
+
+/// A level from a custom pack, stored by its key instead of the index
+#[derive(Debug, Clone)]
+pub struct CustomLevel {
+    pub level: GameLevel,
+    pub pack_key: String,
+    pub score_key: String,
+}
+
+/// A generated level of an endless run, only its stats are stored, under a single key
+#[derive(Debug, Clone)]
+pub struct Endless {
+    pub seed: u64,
+    pub depth: u32,
+    level: GameLevel,
+}
+
+const ENDLESS_STATS_KEY: &str = "endless";
+
+/// The level of the day, only the first attempt of the day is scored
+#[derive(Debug, Clone)]
+pub struct Daily {
+    pub date: Date,
+    pub scored: bool,
+    level: GameLevel,
+}
+
+const DAILY_STATS_KEY: &str = "daily";
+
+/// Copies the summary of the day, and shows it in case the clipboard is not there
+pub fn share_daily(context: &Context<QuantumLoops>, date: Date) {
+    let text = context.storage().daily.share_text(date);
+    let message = match engine::copy_to_clipboard(&text) {
+        Ok(()) => format!("Copied to the clipboard:\n\n{}", text),
+        Err(_) => text,
+    };
+    let _ = engine::window().alert_with_message(&message);
+}
+
+/// A level which is not (yet) in the levels file, nothing is stored about it
+#[derive(Debug)]
+struct Playtest {
+    level: GameLevel,
+    end_timer: f64,
+}
+
+const REPLAY_END_DELAY: f64 = 2.0;
+
 pub fn draw_background(context: &Context<QuantumLoops>, offset: Vector2<f64>) {
     let size = context.surface().size();
     let surface = context.surface().context();
@@ -88,149 +170,404 @@ pub fn draw_background(context: &Context<QuantumLoops>, offset: Vector2<f64>) {
     }
 }
 
-const POWER_USED_PER_PIXEL_PER_SECOND: f64 = 1.0;
+// adds the outline of a ring around `pos` to the current path
+fn trace_ring(surface: &CanvasRenderingContext2d, shape: &Shape, pos: Vector2<f64>, radius: f64) {
+    match *shape {
+        Shape::Circle => surface.arc(pos.x, pos.y, radius, 0.0, TAU).unwrap(),
+        Shape::Ellipse { ratio, rotation } => surface
+            .ellipse(
+                pos.x,
+                pos.y,
+                radius,
+                radius * ratio,
+                rotation.to_radians(),
+                0.0,
+                TAU,
+            )
+            .unwrap(),
+        _ => {
+            for (i, p) in shape.outline().iter().enumerate() {
+                let p = pos + p * radius;
+                if i == 0 {
+                    surface.move_to(p.x, p.y);
+                } else {
+                    surface.line_to(p.x, p.y);
+                }
+            }
+            surface.close_path();
+        }
+    }
+}
+
+/// Strokes the ring around `pos`, only the parts of it that are there
+pub fn stroke_ring(
+    surface: &CanvasRenderingContext2d,
+    ring: &EnergyRing,
+    pos: Vector2<f64>,
+    radius: f64,
+) {
+    let partial = ring.arc.is_some() || !ring.gaps.is_empty();
+    if partial {
+        // the parts are cut out of the whole outline with wedges
+        let reach = radius * ring.shape.extent().norm() + ring.width * 2.0;
+        surface.save();
+        surface.begin_path();
+        for (start, end) in ring.drawn_spans() {
+            surface.move_to(pos.x, pos.y);
+            surface.arc(pos.x, pos.y, reach, start, end).unwrap();
+            surface.close_path();
+        }
+        surface.clip();
+    }
+    surface.begin_path();
+    trace_ring(surface, &ring.shape, pos, radius);
+    surface.stroke();
+    if partial {
+        surface.restore();
+    }
+}
+
+pub fn format_time(seconds: f64) -> String {
+    format!("{:.2}s", seconds)
+}
+
+/// Shields are thick walls and no-cut zones are hatched red circles,
+/// the ones that only cost more energy are fainter than the blocking ones
+pub fn draw_obstacles(
+    surface: &CanvasRenderingContext2d,
+    obstacles: &[Obstacle],
+    center: Vector2<f64>,
+    min_dim: f64,
+    time: f64,
+) {
+    for obstacle in obstacles {
+        surface.set_global_alpha(if obstacle.blocks() { 1.0 } else { 0.5 });
+        match obstacle.kind {
+            ObstacleKind::Shield { .. } => {
+                let (a, b) = obstacle.shield_at(time).expect("a shield");
+                let (a, b) = (center + a * min_dim, center + b * min_dim);
+                surface.set_stroke_style(&SHIELD_COLOR.into());
+                surface.set_line_width(6.0);
+                surface.set_line_cap("round");
+                surface.begin_path();
+                surface.move_to(a.x, a.y);
+                surface.line_to(b.x, b.y);
+                surface.stroke();
+                surface.set_line_cap("butt");
+            }
+            ObstacleKind::NoCut {
+                center: zone,
+                radius,
+            } => {
+                let pos = center + zone * min_dim;
+                let radius = radius * min_dim;
+                surface.set_stroke_style(&NO_CUT_COLOR.into());
+                surface.set_line_width(2.0);
+                surface.save();
+                surface.begin_path();
+                surface.arc(pos.x, pos.y, radius, 0.0, TAU).unwrap();
+                surface.stroke();
+                surface.clip();
+                surface.set_line_width(1.0);
+                surface.begin_path();
+                let mut x = -radius * 2.0;
+                while x < radius * 2.0 {
+                    surface.move_to(pos.x + x - radius, pos.y + radius);
+                    surface.line_to(pos.x + x + radius, pos.y - radius);
+                    x += 8.0;
+                }
+                surface.stroke();
+                surface.restore();
+            }
+        }
+    }
+    surface.set_global_alpha(1.0);
+}
+
+/// The base energy of the ring, the link group it is in and where it has to be cut
+pub fn ring_label(level: &GameLevel, ring: usize) -> String {
+    let mut label = format!("{:.2}", level.rings[ring].base_energy);
+    if let Some(group) = level.link_group(ring) {
+        label += &format!(" ⛓{}", group + 1);
+    }
+    if level.rings[ring].reverse {
+        label += " ⇄";
+    }
+    match level.rings[ring].cut_rule {
+        CutRule::Anywhere => {}
+        CutRule::Ahead => label += " ahead",
+        CutRule::Behind => label += " behind",
+    }
+    label
+}
 
 impl MainGameState {
     pub fn new(level_idx: usize) -> Self {
         Self {
-            level: None,
             level_idx,
-            current_ring: 0,
+            simulation: None,
+            recording: None,
+            frame: Frame::default(),
+            mode: Mode::Campaign,
+            play_mode: PlayMode::Normal,
+            prev_particle_angles: Vec::new(),
             energy: SmoothChange::new(100.0, 50.0),
-            disruption: None,
-            particle_angle: 0.0,
             game_status: GameStatus::Playing,
             noise: Perlin::new(),
         }
     }
 
-    pub fn resume(&mut self) {
-        self.game_status = GameStatus::Playing;
+    pub fn replay(recording: Recording) -> Self {
+        let mut state = Self::new(recording.level_idx);
+        state.energy.set_raw(recording.level.energy);
+        state.simulation = Some(recording.simulation());
+        state.recording = Some(recording);
+        state.mode = Mode::Replay(Replay {
+            next_frame: 0,
+            end_timer: 0.0,
+        });
+        state
     }
 
-    pub fn level_idx(&self) -> usize {
-        self.level_idx
+    pub fn playtest(level: GameLevel) -> Self {
+        let mut state = Self::new(0);
+        state.mode = Mode::Playtest(Playtest {
+            level,
+            end_timer: 0.0,
+        });
+        state
     }
 
-    fn check_level(
-        &mut self,
-        context: &mut Context<QuantumLoops>,
-    ) -> Option<StateTransition<QuantumLoops>> {
-        if self.level.is_some() {
-            return None;
+    pub fn custom(custom: CustomLevel) -> Self {
+        let mut state = Self::new(0);
+        state.mode = Mode::Custom(custom);
+        state
+    }
+
+    pub fn endless(seed: u64, depth: u32) -> Self {
+        let mut state = Self::new(0);
+        state.mode = Mode::Endless(Endless {
+            seed,
+            depth,
+            level: generator::endless_level(seed, depth),
+        });
+        state
+    }
+
+    pub fn daily(date: Date) -> Self {
+        let mut state = Self::new(0);
+        state.mode = Mode::Daily(Daily {
+            date,
+            scored: false,
+            level: daily::daily_level(date),
+        });
+        state
+    }
+
+    pub fn with_play_mode(mut self, play_mode: PlayMode) -> Self {
+        self.play_mode = play_mode;
+        self
+    }
+
+    /// A fresh attempt at the same level
+    pub fn restart(&self) -> Self {
+        let mut state = match &self.mode {
+            Mode::Playtest(playtest) => Self::playtest(playtest.level.clone()),
+            _ => Self::new(self.level_idx),
+        };
+        match &self.mode {
+            Mode::Custom(custom) => state.mode = Mode::Custom(custom.clone()),
+            Mode::Endless(endless) => state.mode = Mode::Endless(endless.clone()),
+            Mode::Daily(daily) => state.mode = Mode::Daily(daily.clone()),
+            Mode::Campaign | Mode::Playtest(_) | Mode::Replay(_) => {}
         }
-        let level = context.game.get_level(self.level_idx);
-        if level.is_none() {
-            return Some(StateTransition::None);
+        state.play_mode = self.play_mode;
+        state
+    }
+
+    pub fn custom_level(&self) -> Option<&CustomLevel> {
+        match &self.mode {
+            Mode::Custom(custom) => Some(custom),
+            _ => None,
         }
-        self.energy.set_raw(level.as_ref().unwrap().energy);
-        self.level = level;
-        None
     }
 
-    fn start_disruption(&mut self, pos: Vector2<f64>, cause: DisruptionCause) {
-        self.disruption = Some(Disruption {
-            start: pos,
-            end: pos,
-            start_time: engine::time(),
-            cause,
-        })
+    pub fn endless_run(&self) -> Option<&Endless> {
+        match &self.mode {
+            Mode::Endless(endless) => Some(endless),
+            _ => None,
+        }
     }
 
-    fn update_disruption(
-        &mut self,
-        pos: Vector2<f64>,
-        dragging: bool,
-        context: &mut Context<QuantumLoops>,
-    ) {
-        if let Some(d) = self.disruption.as_mut() {
-            if dragging {
-                d.end = pos;
-            } else if engine::time() - d.start_time >= 0.01 {
-                self.finish_disruption(Some(pos), context);
-            }
+    pub fn daily_challenge(&self) -> Option<&Daily> {
+        match &self.mode {
+            Mode::Daily(daily) => Some(daily),
+            _ => None,
         }
     }
 
-    fn finish_disruption(
-        &mut self,
-        pos: Option<Vector2<f64>>,
-        context: &mut Context<QuantumLoops>,
-    ) {
-        if let Some(mut d) = self.disruption.take() {
-            if let Some(pos) = pos {
-                d.end = pos;
-            }
+    pub fn play_mode(&self) -> PlayMode {
+        self.play_mode
+    }
 
-            let center = context.surface().size() / 2.0;
+    /// How long the level has been played for, in seconds
+    pub fn elapsed(&self) -> f64 {
+        self.simulation.as_ref().map_or(0.0, Simulation::time)
+    }
 
-            let dist = d.start.metric_distance(&d.end);
-            let time = engine::time() - d.start_time;
+    /// The key of the best time of the level, `None` outside of the time attack mode
+    pub fn best_time_key(&self) -> Option<String> {
+        let simulation = self.simulation.as_ref()?;
+        if self.play_mode != PlayMode::TimeAttack || self.is_playtest() || self.is_replay() {
+            return None;
+        }
+        Some(self.stats_key(simulation.level()))
+    }
 
-            let level = self.level.as_mut().unwrap();
-            let mut intersections = level
-                .rings
-                .iter_mut()
-                .enumerate()
-                .filter(|(_, r)| r.disrupted_time <= 1.0 && r.intersects(center, &d))
-                .collect::<Vec<_>>();
+    /// Whether anything about the attempt is stored, the stats, the scores and the progress
+    pub fn is_stored(&self) -> bool {
+        !self.is_playtest() && !self.is_replay() && self.play_mode != PlayMode::Practice
+    }
 
-            let extras = if intersections.len() == 1 {
-                let (idx, ring) = &mut intersections[0];
-                ring.disrupted_time = if *idx == self.current_ring {
-                    ring.restore_time
-                } else {
-                    JIGGLE_TIME
-                };
-                ring.base_energy
-            } else {
-                intersections.iter_mut().map(|(_, r)| r.base_energy).sum()
-            };
+    pub fn can_undo(&self) -> bool {
+        self.play_mode == PlayMode::Practice
+            && !self.is_replay()
+            && matches!(&self.simulation, Some(simulation) if simulation.can_undo())
+    }
 
-            self.energy
-                .set(self.energy.get() - dist * time * POWER_USED_PER_PIXEL_PER_SECOND - extras);
+    /// Steps back to before the last disruption at the start of the next frame,
+    /// it is recorded so that the replays still follow
+    pub fn undo(&mut self) {
+        if self.can_undo() {
+            self.frame.undo = true;
+            self.game_status = GameStatus::Playing;
         }
     }
 
-    fn update_particle_level(
+    /// Whether the level is one from the levels file, the progress is only kept for those
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.mode, Mode::Campaign)
+    }
+
+    // the custom levels might have the same names as the ones from the levels file
+    fn stats_key(&self, level: &GameLevel) -> String {
+        match &self.mode {
+            Mode::Custom(custom) => custom.score_key.clone(),
+            Mode::Endless(_) => ENDLESS_STATS_KEY.into(),
+            Mode::Daily(_) => DAILY_STATS_KEY.into(),
+            Mode::Campaign | Mode::Playtest(_) | Mode::Replay(_) => level.name.clone(),
+        }
+    }
+
+    fn is_replay(&self) -> bool {
+        matches!(self.mode, Mode::Replay(_))
+    }
+
+    fn is_playtest(&self) -> bool {
+        matches!(self.mode, Mode::Playtest(_))
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn resume(&mut self) {
+        self.game_status = GameStatus::Playing;
+    }
+
+    pub fn level_idx(&self) -> usize {
+        self.level_idx
+    }
+
+    fn check_level(
         &mut self,
         context: &mut Context<QuantumLoops>,
-        play_sound: bool,
-    ) -> bool {
-        let jump_to = self
-            .level
-            .as_mut()
-            .unwrap()
-            .rings
-            .iter()
-            .enumerate()
-            .filter(|(_, r)| r.disrupted_time <= 0.0)
-            .max_by(|(_, r1), (_, r2)| {
-                r1.base_energy
-                    .partial_cmp(&r2.base_energy)
-                    .expect("NaN not allowed")
-            })
-            .map(|(idx, _)| idx);
+    ) -> Option<StateTransition<QuantumLoops>> {
+        if self.simulation.is_some() {
+            return None;
+        }
+        let level = match &self.mode {
+            Mode::Playtest(Playtest { level, .. })
+            | Mode::Custom(CustomLevel { level, .. })
+            | Mode::Endless(Endless { level, .. })
+            | Mode::Daily(Daily { level, .. }) => level.clone(),
+            Mode::Campaign | Mode::Replay(_) => match context.game.get_level(self.level_idx) {
+                Some(level) => level,
+                None => return Some(StateTransition::None),
+            },
+        };
+        let size = context.surface().size();
+        self.energy.set_raw(level.energy);
+
+        if self.is_stored() {
+            let mut storage = context.storage().clone();
+            storage.level_stats_mut(&self.stats_key(&level)).attempts += 1;
+            if let Mode::Daily(daily) = &mut self.mode {
+                daily.scored = !storage.daily.played(daily.date);
+                if daily.scored {
+                    storage.daily.start(daily.date);
+                }
+            }
+            context.set_storage(storage);
+        }
 
-        if let Some(idx) = jump_to {
-            if self.current_ring != idx {
-                self.current_ring = idx;
-                if play_sound {
-                    context.game.sounds.jump.play();
+        self.recording = Some(Recording::new(self.level_idx, level.clone(), size));
+        let mut simulation = Simulation::new(level, size);
+        if self.play_mode == PlayMode::Practice {
+            simulation.keep_history();
+        }
+        self.simulation = Some(simulation);
+        None
+    }
+
+    fn update_simulation(&mut self, context: &mut Context<QuantumLoops>) {
+        let simulation = self.simulation.as_mut().unwrap();
+        self.prev_particle_angles = simulation.particles().iter().map(|p| p.angle).collect();
+
+        let jumped = if let Mode::Replay(replay) = &mut self.mode {
+            let recording = self.recording.as_ref().unwrap();
+            match recording.frames.get(replay.next_frame) {
+                Some(frame) => {
+                    replay.next_frame += 1;
+                    Recording::apply_frame(frame, simulation)
+                }
+                None => {
+                    replay.end_timer += context.delta_time();
+                    false
                 }
             }
-            false
+        } else if let GameStatus::Playing = self.game_status {
+            let size = context.surface().size();
+            if simulation.size() != size {
+                self.frame.size = Some(size);
+            }
+            self.frame.delta_time = context.delta_time();
+
+            let frame = std::mem::take(&mut self.frame);
+            let jumped = Recording::apply_frame(&frame, simulation);
+
+            let recording = self.recording.as_mut().unwrap();
+            recording.frames.push(frame);
+            if let Status::Won { score } = simulation.status() {
+                recording.score = Some(score);
+            }
+            jumped
         } else {
-            true
+            return;
+        };
+
+        if jumped {
+            context.game.sounds.jump.play();
+        }
+        if simulation.jiggling() {
+            context.game.sounds.wrong_ring.play_unique();
         }
     }
 }
 
 impl GameState<QuantumLoops> for MainGameState {
     fn on_pushed(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
-        if self.check_level(context).is_some() {
-            self.update_particle_level(context, false);
-        }
+        self.check_level(context);
         StateTransition::None
     }
 
@@ -243,76 +580,125 @@ impl GameState<QuantumLoops> for MainGameState {
             return transition;
         }
         log::debug!("event {:?}", event);
-        match &event {
+        if self.is_replay() {
+            return match event {
+                Event::KeyDown { code: 27, .. } => StateTransition::Pop,
+                _ => StateTransition::None,
+            };
+        }
+        match event {
+            Event::KeyDown { code: 27, .. } if self.is_playtest() => {
+                return StateTransition::Pop;
+            }
             Event::KeyDown { code: 27, .. } => {
                 self.game_status = GameStatus::Paused;
                 return StateTransition::Pop;
             }
             Event::KeyDown { code: 82, .. } => {
-                return StateTransition::set(MainGameState::new(self.level_idx));
+                return StateTransition::set(self.restart());
             }
-
-            Event::MouseDown {
-                pos,
-                button: MouseButton::Left,
-            } => self.start_disruption(*pos, DisruptionCause::Mouse),
-
-            Event::MouseMove { pos, buttons } => {
-                if let Some(DisruptionCause::Mouse) = self.disruption.as_ref().map(|d| &d.cause) {
-                    self.update_disruption(
-                        *pos,
-                        buttons.contains(&MouseButton::Left),
-                        context,
-                    )
-                }
+            Event::KeyDown { code: 85, .. } => {
+                self.undo();
+                return StateTransition::None;
             }
-
-            Event::MouseUp {
-                pos,
-                button: MouseButton::Left,
-            } => {
-                if let Some(DisruptionCause::Mouse) = self.disruption.as_ref().map(|d| &d.cause) {
-                    self.finish_disruption(Some(*pos), context)
-                }
+            _ => {}
+        }
+        if let GameStatus::Playing = self.game_status {
+            if Input::from_event(&event).is_some() {
+                self.frame.events.push(event);
             }
+        }
+        StateTransition::None
+    }
 
-            Event::TouchStart { touches } if touches.len() == 1 => {
-                self.start_disruption(touches[0], DisruptionCause::Touch)
-            }
+    fn on_fixed_update(
+        &mut self,
+        context: &mut Context<QuantumLoops>,
+    ) -> StateTransition<QuantumLoops> {
+        if let Some(transition) = self.check_level(context) {
+            return transition;
+        }
+
+        self.update_simulation(context);
 
-            Event::TouchMove { touches } if touches.len() == 1 => {
-                if let Some(DisruptionCause::Touch) = self.disruption.as_ref().map(|d| &d.cause) {
-                    self.update_disruption(touches[0], true, context)
+        let simulation = self.simulation.as_ref().unwrap();
+
+        if let Mode::Replay(replay) = &self.mode {
+            if replay.end_timer >= REPLAY_END_DELAY {
+                return StateTransition::Pop;
+            }
+        } else if let Mode::Playtest(playtest) = &mut self.mode {
+            if !simulation.is_playing() {
+                playtest.end_timer += context.delta_time();
+                if playtest.end_timer >= REPLAY_END_DELAY {
+                    return StateTransition::Pop;
                 }
             }
-
-            Event::TouchEnd { touches } if touches.len() <= 1 => {
-                if let Some(DisruptionCause::Touch) = self.disruption.as_ref().map(|d| &d.cause) {
-                    self.finish_disruption(touches.get(0).copied(), context)
+        } else if let GameStatus::Playing = self.game_status {
+            if !simulation.is_playing() && self.is_stored() {
+                let mut storage = context.storage().clone();
+                storage
+                    .level_stats_mut(&self.stats_key(simulation.level()))
+                    .record(simulation);
+                context.set_storage(storage);
+            }
+            match simulation.status() {
+                Status::Playing => {}
+                Status::Lost => {
+                    self.game_status = GameStatus::Lost;
+                    return StateTransition::Pop;
+                }
+                Status::Won { score } => {
+                    let storage = context.storage();
+                    if self.is_builtin()
+                        && self.is_stored()
+                        && storage.unlocked_level < self.level_idx + 1
+                    {
+                        let new_storage = StoredData {
+                            unlocked_level: self.level_idx + 1,
+                            ..storage.clone()
+                        };
+                        context.set_storage(new_storage);
+                    }
+                    self.game_status = GameStatus::Won { score };
+                    return StateTransition::Pop;
                 }
             }
-
-            _ => {}
         }
+
         StateTransition::None
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
-        if let Some(transition) = self.check_level(context) {
-            return transition;
-        }
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        let simulation = match self.simulation.as_ref() {
+            Some(simulation) => simulation,
+            None => return StateTransition::None,
+        };
 
-        // render:
+        self.energy.set(simulation.energy());
+        self.energy.update(context.delta_time());
 
-        let size = context.surface().size();
-        let center = size / 2.0;
+        // render:
 
-        draw_background(&context, center);
+        let screen_size = context.surface().size();
+        draw_background(&context, screen_size / 2.0);
 
         let surface = context.surface().context();
 
+        // replays are simulated in the recorded viewport, so it is scaled to fit the current one
+        let size = simulation.size();
+        let scale = (screen_size.x / size.x).min(screen_size.y / size.y);
+        let shift = (screen_size - size * scale) / 2.0;
+        surface
+            .set_transform(scale, 0.0, 0.0, scale, shift.x, shift.y)
+            .unwrap();
+
+        let center = size / 2.0;
+
+        let level = simulation.level();
+
         let energy = self.energy.get_interp();
-        let w = size.x * energy / self.level.as_mut().unwrap().energy;
+        let w = size.x * energy / level.energy;
         surface.set_fill_style(&ENERGY_BAR_COLOR.into());
         surface.fill_rect(0.0, 0.0, w, context.rem_to_px(1.0));
 
@@ -325,22 +711,51 @@ impl GameState<QuantumLoops> for MainGameState {
                 context.rem_to_px(1.6),
             )
             .unwrap();
+        if self.play_mode == PlayMode::TimeAttack {
+            surface
+                .fill_text(
+                    &format_time(simulation.time()),
+                    size.x - context.rem_to_px(3.0),
+                    context.rem_to_px(1.6),
+                )
+                .unwrap();
+        }
 
         let min_dim = size.min();
-
-        let mut jiggling = 0.0;
-
-        let level = self.level.as_mut().unwrap();
-        for (idx, ring) in level.rings.iter_mut().enumerate() {
+        let particle_angles = simulation
+            .particles()
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let prev = self
+                    .prev_particle_angles
+                    .get(idx)
+                    .copied()
+                    .unwrap_or(p.angle);
+                prev + (p.angle - prev) * context.alpha()
+            })
+            .collect::<Vec<_>>();
+        let show_particle = simulation.is_playing()
+            && (self.is_replay() || matches!(self.game_status, GameStatus::Playing));
+
+        draw_obstacles(
+            &surface,
+            &level.obstacles,
+            center,
+            min_dim,
+            simulation.time(),
+        );
+
+        let offsets = level.ring_offsets(simulation.time());
+        for (idx, ring) in level.rings.iter().enumerate() {
             surface.set_stroke_style(&(&ring.color).into());
 
-            let mut pos = center + ring.offset * min_dim;
+            let mut pos = center + offsets[idx] * min_dim;
 
             if ring.disrupted_time > 0.0 && ring.disrupted_time <= JIGGLE_TIME {
-                let offset = self.particle_angle * 5.0;
+                let offset = particle_angles[0] * 5.0;
                 pos.x += (self.noise.get([0.0, offset]) * 2.0 - 1.0) * 2.0;
                 pos.y += (self.noise.get([offset, 0.0]) * 2.0 - 1.0) * 2.0;
-                jiggling = ring.disrupted_time;
             }
 
             let radius = min_dim * ring.radius;
@@ -348,44 +763,37 @@ impl GameState<QuantumLoops> for MainGameState {
             surface.set_global_alpha(1.0 - ring.disrupted_time / ring.restore_time);
 
             surface.set_line_width(ring.width);
-            surface.begin_path();
-            surface.arc(pos.x, pos.y, radius, 0.0, TAU).unwrap();
-            surface.stroke();
+            stroke_ring(&surface, ring, pos, radius);
 
             surface.set_global_alpha(1.0);
 
-            let tpx = pos.x + (radius + context.rem_to_px(1.5)) * FRAC_PI_4.cos();
-            let tpy = pos.y + (radius + context.rem_to_px(1.5)) * FRAC_PI_4.sin();
+            let edge = ring.shape.point_at(FRAC_PI_4).norm() * radius;
+            let tpx = pos.x + (edge + context.rem_to_px(1.5)) * FRAC_PI_4.cos();
+            let tpy = pos.y + (edge + context.rem_to_px(1.5)) * FRAC_PI_4.sin();
 
             surface.set_fill_style(&TEXT_COLOR.into());
             surface.set_font("0.9rem monospace");
             surface
-                .fill_text(&format!("{:.2}", ring.base_energy), tpx, tpy)
+                .fill_text(&ring_label(level, idx), tpx, tpy)
                 .unwrap();
 
-            if let GameStatus::Playing = self.game_status {
-                if ring.disrupted_time > 0.0 {
-                    ring.disrupted_time -= context.delta_time();
-                }
-                if idx == self.current_ring {
-                    let px = pos.x + radius * self.particle_angle.cos();
-                    let py = pos.y + radius * self.particle_angle.sin();
-
-                    surface.set_fill_style(&"blue".into());
-                    surface.begin_path();
-                    surface.arc(px, py, 7.0, 0.0, TAU).unwrap();
-                    surface.fill();
-                }
+            if !show_particle {
+                continue;
             }
-        }
+            for (particle, angle) in simulation.particles().iter().zip(&particle_angles) {
+                if particle.ring != Some(idx) {
+                    continue;
+                }
+                let particle = pos + ring.shape.point_at(*angle) * radius;
 
-        if let GameStatus::Playing = self.game_status {
-            if jiggling != 0.0 {
-                context.game.sounds.wrong_ring.play_unique();
+                surface.set_fill_style(&"blue".into());
+                surface.begin_path();
+                surface.arc(particle.x, particle.y, 7.0, 0.0, TAU).unwrap();
+                surface.fill();
             }
         }
 
-        if let Some(Disruption { start, end, .. }) = self.disruption.as_ref() {
+        if let Some(Disruption { start, end, .. }) = simulation.disruption() {
             surface.set_stroke_style(&"red".into());
             surface.set_line_width(1.0);
             surface.begin_path();
@@ -394,34 +802,30 @@ impl GameState<QuantumLoops> for MainGameState {
             surface.stroke();
         }
 
-        self.energy.update(context.delta_time());
-
-        if self.energy.get() <= 0.0 {
-            self.game_status = GameStatus::Lost;
-            return StateTransition::Pop;
-        }
-
-        if let GameStatus::Playing = self.game_status {
-            if self.update_particle_level(context, true) {
-                let level = self.level.as_ref().unwrap();
-
-                let storage = context.storage();
-                if storage.unlocked_level < self.level_idx + 1 {
-                    let new_storage = StoredData {
-                        unlocked_level: self.level_idx + 1,
-                        ..storage.clone()
-                    };
-                    context.set_storage(new_storage);
-                }
-
-                let free = level.energy - level.rings.iter().map(|r| r.base_energy).sum::<f64>();
-
-                self.game_status = GameStatus::Won {
-                    score: (1.0 - (free - self.energy.get()) / free) * 100.0,
-                };
-                return StateTransition::Pop;
-            }
-            self.particle_angle += TAU * context.delta_time();
+        surface.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0).unwrap();
+
+        let label = match &self.mode {
+            Mode::Replay(_) => Some("REPLAY".to_owned()),
+            Mode::Playtest(_) => Some(match simulation.status() {
+                Status::Playing => "PLAYTEST".to_owned(),
+                Status::Won { score } => format!("WON {:.2}%", score),
+                Status::Lost => "LOST".to_owned(),
+            }),
+            _ if self.play_mode == PlayMode::Practice => Some("PRACTICE, U to undo".to_owned()),
+            Mode::Daily(daily) if daily.scored => Some(format!("DAILY {}", daily.date)),
+            Mode::Daily(daily) => Some(format!("DAILY {} (not scored)", daily.date)),
+            Mode::Campaign | Mode::Custom(_) | Mode::Endless(_) => None,
+        };
+        if let Some(label) = label {
+            surface.set_fill_style(&TEXT_COLOR.into());
+            surface.set_font("1.5rem monospace");
+            surface
+                .fill_text(
+                    &label,
+                    screen_size.x / 2.0,
+                    screen_size.y - context.rem_to_px(2.0),
+                )
+                .unwrap();
         }
 
         StateTransition::None
@@ -431,6 +835,9 @@ impl GameState<QuantumLoops> for MainGameState {
         self: Box<Self>,
         context: &mut Context<QuantumLoops>,
     ) -> StateTransition<QuantumLoops> {
+        if self.is_replay() || self.is_playtest() {
+            return StateTransition::None;
+        }
         match self.game_status {
             GameStatus::Won { score } => {
                 context.game.sounds.win.play();
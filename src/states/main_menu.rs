@@ -1,10 +1,17 @@
 use noise::{NoiseFn, Perlin};
 
 use crate::{
+    daily::Date,
     engine::{self, event::Event, ui::Button, *},
     states::{
-        level_select::LevelMenuState, main_game::draw_background, options::OptionsState,
-        scores::ScoresState, tutorial::TutorialState,
+        level_editor::LevelEditorState,
+        level_select::LevelMenuState,
+        main_game::{draw_background, MainGameState, TEXT_COLOR},
+        options::OptionsState,
+        profiles::ProfilesState,
+        scores::ScoresState,
+        stats::StatsState,
+        tutorial::TutorialState,
     },
     QuantumLoops,
 };
@@ -38,8 +45,12 @@ impl Background {
 pub struct MainMenuState {
     background: Background,
     play: Button,
+    daily: Button,
     scores: Button,
+    stats: Button,
     options: Button,
+    profile: Button,
+    editor: Button,
     exit: Button,
 }
 
@@ -48,8 +59,12 @@ impl MainMenuState {
         Self {
             background: Background::new(),
             play: Button::new("Play".into()),
+            daily: Button::empty(),
             scores: Button::new("Scores".into()),
+            stats: Button::new("Stats".into()),
             options: Button::new("Options".into()),
+            profile: Button::empty().with_size(1.5),
+            editor: Button::new("Level editor".into()).with_size(1.5),
             exit: Button::new("Exit".into()),
         }
     }
@@ -72,27 +87,69 @@ impl GameState<QuantumLoops> for MainMenuState {
             } else {
                 Box::new(TutorialState::new())
             });
+        } else if self.daily.on_event(&event, context) {
+            let (year, month, day) = engine::today();
+            return StateTransition::set(MainGameState::daily(Date::new(year, month, day)));
         } else if self.scores.on_event(&event, context) {
             return StateTransition::push(ScoresState::new());
+        } else if self.stats.on_event(&event, context) {
+            return StateTransition::push(StatsState::new());
         } else if self.options.on_event(&event, context) {
             return StateTransition::set(OptionsState::new());
+        } else if self.profile.on_event(&event, context) {
+            return StateTransition::set(ProfilesState::new());
+        } else if self.editor.on_event(&event, context) {
+            return StateTransition::set(LevelEditorState::empty());
         } else if self.exit.on_event(&event, context) {
             engine::window().history().unwrap().back().unwrap();
         }
         StateTransition::None
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
         let center = context.surface().size() / 2.0;
         let offset: Vector2<f64> = [0.0, context.rem_to_px(2.5)].into();
 
         context.game.sounds.background.play_unique();
 
         self.background.on_update(context);
-        self.play.on_update(context, center - offset * 2.0);
+        let (year, month, day) = engine::today();
+        let daily = match context
+            .storage()
+            .daily
+            .history
+            .get(&Date::new(year, month, day).to_string())
+        {
+            Some(Some(score)) => format!("Daily: {:.2}%", score),
+            Some(None) => "Daily: lost".into(),
+            None => "Daily challenge".into(),
+        };
+        self.daily.set_text(daily.into());
+
+        self.play.on_update(context, center - offset * 3.0);
+        self.daily.on_update(context, center - offset * 2.0);
         self.scores.on_update(context, center - offset);
-        self.options.on_update(context, center);
-        self.exit.on_update(context, center + offset);
+        self.stats.on_update(context, center);
+        self.options.on_update(context, center + offset);
+        self.exit.on_update(context, center + offset * 2.0);
+
+        let profile = format!("Profile: {}", context.profiles().active());
+        self.profile.set_text(profile.into());
+        self.profile.on_update(context, center + offset * 3.0);
+        self.editor.on_update(context, center + offset * 3.75);
+
+        if !context.is_storage_persistent() {
+            let surface = context.surface().context();
+            surface.set_fill_style(&TEXT_COLOR.into());
+            surface.set_font("1rem monospace");
+            surface
+                .fill_text(
+                    "Your progress can not be saved in this browser",
+                    center.x,
+                    center.y + offset.y * 4.5,
+                )
+                .unwrap();
+        }
 
         StateTransition::None
     }
@@ -1,9 +1,13 @@
 pub mod game_lost;
 pub mod game_won;
+pub mod import;
+pub mod level_editor;
 pub mod level_select;
 pub mod main_game;
 pub mod main_menu;
 pub mod options;
 pub mod pause;
+pub mod profiles;
 pub mod scores;
+pub mod stats;
 pub mod tutorial;
@@ -1,15 +1,48 @@
 use nalgebra::Vector2;
 
 use crate::{
-    engine::{event::Event, ui::Button, Context, GameState, StateTransition},
+    engine::{self, event::Event, ui::Button, Context, GameState, StateTransition},
     level::StoredData,
+    save_code,
     states::{
+        import::ImportState,
         main_menu::{Background, MainMenuState},
         tutorial::TutorialState,
     },
     QuantumLoops,
 };
 
+// the file has everything, the code is short enough to be copied by hand
+fn export(context: &Context<QuantumLoops>) {
+    let window = engine::window();
+    let file_name = format!("quantum-loops-{}.json", context.profiles().active());
+    if let Err(e) = engine::download(&file_name, &save_code::export_json(context.storage())) {
+        log::error!("Failed to download the progress: {:?}", e);
+    }
+    let _ = window.prompt_with_message_and_default(
+        "Your save code, paste it in \"Import progress\" on another device",
+        &save_code::export_code(context.storage()),
+    );
+}
+
+fn import() -> Option<StoredData> {
+    let window = engine::window();
+    let text = window
+        .prompt_with_message("Paste a save code or the contents of an exported file")
+        .ok()
+        .flatten()?;
+    if text.trim().is_empty() {
+        return None;
+    }
+    match save_code::import(&text) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            let _ = window.alert_with_message(&format!("Could not import: {}", e));
+            None
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct OptionsState {
     background: Background,
@@ -18,6 +51,8 @@ pub struct OptionsState {
     tutorial: Button,
     sounds: Button,
     music: Button,
+    export: Button,
+    import: Button,
     sure_timer: f64,
 }
 
@@ -30,6 +65,8 @@ impl OptionsState {
             tutorial: Button::new("Open the tutorial".into()),
             sounds: Button::empty(),
             music: Button::empty(),
+            export: Button::new("Export progress".into()),
+            import: Button::new("Import progress".into()),
             sure_timer: 0.0,
         }
     }
@@ -55,14 +92,20 @@ impl GameState<QuantumLoops> for OptionsState {
             return StateTransition::set(TutorialState::new());
         } else if self.sounds.on_event(&event, context) {
             let data = context.storage().clone();
-            context.sound_context_mut().sound_mask.set(0, !data.sounds_enabled);
+            context
+                .sound_context_mut()
+                .sound_mask
+                .set(0, !data.sounds_enabled);
             context.set_storage(StoredData {
                 sounds_enabled: !data.sounds_enabled,
                 ..data
             });
         } else if self.music.on_event(&event, context) {
             let data = context.storage().clone();
-            context.sound_context_mut().sound_mask.set(1, !data.music_enabled);
+            context
+                .sound_context_mut()
+                .sound_mask
+                .set(1, !data.music_enabled);
             let bg = &context.game.sounds.background;
             if data.music_enabled {
                 bg.stop();
@@ -73,13 +116,19 @@ impl GameState<QuantumLoops> for OptionsState {
                 music_enabled: !data.music_enabled,
                 ..data
             });
+        } else if self.export.on_event(&event, context) {
+            export(context);
+        } else if self.import.on_event(&event, context) {
+            if let Some(imported) = import() {
+                return StateTransition::push(ImportState::new(context.storage(), imported));
+            }
         } else if self.back.on_event(&event, context) {
             return StateTransition::set(MainMenuState::new());
         }
         StateTransition::None
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
         let center = context.surface().size() / 2.0;
         let offset: Vector2<f64> = [0.0, context.rem_to_px(2.5)].into();
 
@@ -116,11 +165,13 @@ impl GameState<QuantumLoops> for OptionsState {
             .into(),
         );
 
-        self.back.on_update(context, center - offset * 2.0);
-        self.reset.on_update(context, center - offset);
-        self.tutorial.on_update(context, center);
-        self.sounds.on_update(context, center + offset);
-        self.music.on_update(context, center + offset * 2.0);
+        self.back.on_update(context, center - offset * 3.0);
+        self.reset.on_update(context, center - offset * 2.0);
+        self.tutorial.on_update(context, center - offset);
+        self.sounds.on_update(context, center);
+        self.music.on_update(context, center + offset);
+        self.export.on_update(context, center + offset * 2.0);
+        self.import.on_update(context, center + offset * 3.0);
 
         StateTransition::None
     }
@@ -13,6 +13,7 @@ pub struct PauseState {
     retry: Button,
     level_menu: Button,
     resume: Button,
+    undo: Button,
 }
 
 impl PauseState {
@@ -22,6 +23,7 @@ impl PauseState {
             retry: Button::new("Retry".into()).with_size(1.5),
             level_menu: Button::new("Level Menu".into()).with_size(1.5),
             resume: Button::new("Resume".into()),
+            undo: Button::new("Undo".into()).with_size(1.5),
         }
     }
 }
@@ -35,18 +37,21 @@ impl GameState<QuantumLoops> for PauseState {
         if let Event::KeyDown { code: 27, .. } = event {
             StateTransition::Pop
         } else if self.retry.on_event(&event, context) {
-            StateTransition::set(MainGameState::new(self.game_state.level_idx()))
+            StateTransition::set(self.game_state.restart())
         } else if self.resume.on_event(&event, context) {
             StateTransition::Pop
+        } else if self.game_state.can_undo() && self.undo.on_event(&event, context) {
+            self.game_state.undo();
+            StateTransition::Pop
         } else if self.level_menu.on_event(&event, context) {
-            StateTransition::set(LevelMenuState::new())
+            StateTransition::set(LevelMenuState::for_game(&self.game_state))
         } else {
             StateTransition::None
         }
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
-        self.game_state.on_update(context);
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        self.game_state.on_render(context);
 
         let center = context.surface().size() / 2.0;
         let surface = context.surface().context();
@@ -64,6 +69,12 @@ impl GameState<QuantumLoops> for PauseState {
             context,
             [center.x, center.y + context.rem_to_px(3.0)].into(),
         );
+        if self.game_state.can_undo() {
+            self.undo.on_update(
+                context,
+                [center.x, center.y + context.rem_to_px(4.5)].into(),
+            );
+        }
         self.resume.on_update(
             context,
             [center.x, center.y + context.rem_to_px(6.0)].into(),
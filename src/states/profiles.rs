@@ -0,0 +1,157 @@
+use nalgebra::Vector2;
+
+use crate::{
+    engine::{
+        self, event::Event, profiles::MAX_NAME_LENGTH, ui::Button, Context, GameState,
+        StateTransition,
+    },
+    states::main_menu::{Background, MainMenuState},
+    QuantumLoops,
+};
+
+#[derive(Debug)]
+struct ProfileEntry {
+    name: String,
+    select: Button,
+    delete: Button,
+}
+
+#[derive(Debug)]
+pub struct ProfilesState {
+    background: Background,
+    back: Button,
+    create: Button,
+    entries: Vec<ProfileEntry>,
+    // the profile which is going to be deleted on the second click
+    sure: Option<(usize, f64)>,
+}
+
+impl ProfilesState {
+    pub fn new() -> Self {
+        Self {
+            background: Background::new(),
+            back: Button::new(" ← back  ".into()),
+            create: Button::new("New profile".into()),
+            entries: Vec::new(),
+            sure: None,
+        }
+    }
+
+    fn update_entries(&mut self, context: &Context<QuantumLoops>) {
+        let profiles = context.profiles();
+        self.entries = profiles
+            .names()
+            .iter()
+            .map(|name| {
+                let text = if name == profiles.active() {
+                    format!("> {} <", name)
+                } else {
+                    name.clone()
+                };
+                let mut delete = Button::new("delete".into()).with_size(1.5);
+                delete.enabled = profiles.names().len() > 1;
+                ProfileEntry {
+                    name: name.clone(),
+                    select: Button::new(text.into()),
+                    delete,
+                }
+            })
+            .collect();
+        self.sure = None;
+    }
+}
+
+fn ask_for_name() -> Option<String> {
+    engine::window()
+        .prompt_with_message(&format!(
+            "Name of the new profile (up to {} characters)",
+            MAX_NAME_LENGTH
+        ))
+        .ok()
+        .flatten()
+}
+
+impl GameState<QuantumLoops> for ProfilesState {
+    fn on_pushed(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        self.update_entries(context);
+        StateTransition::None
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        context: &mut Context<QuantumLoops>,
+    ) -> StateTransition<QuantumLoops> {
+        if let Event::KeyDown { code: 27, .. } = event {
+            return StateTransition::set(MainMenuState::new());
+        }
+        if self.back.on_event(&event, context) {
+            return StateTransition::set(MainMenuState::new());
+        }
+        if self.create.on_event(&event, context) {
+            if let Some(name) = ask_for_name() {
+                if context.create_profile(&name) {
+                    self.update_entries(context);
+                } else {
+                    log::warn!("Invalid or already used profile name '{}'", name);
+                }
+            }
+            return StateTransition::None;
+        }
+        for idx in 0..self.entries.len() {
+            let entry = &mut self.entries[idx];
+            if entry.select.on_event(&event, context) {
+                let name = entry.name.clone();
+                context.switch_profile(&name);
+                return StateTransition::set(MainMenuState::new());
+            }
+            if entry.delete.on_event(&event, context) {
+                match self.sure {
+                    Some((sure_idx, _)) if sure_idx == idx => {
+                        let name = entry.name.clone();
+                        context.delete_profile(&name);
+                        self.update_entries(context);
+                    }
+                    _ => self.sure = Some((idx, 3.0)),
+                }
+                return StateTransition::None;
+            }
+        }
+        StateTransition::None
+    }
+
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        let center = context.surface().size() / 2.0;
+        let offset: Vector2<f64> = [0.0, context.rem_to_px(2.5)].into();
+        let delete_offset: Vector2<f64> = [context.rem_to_px(18.0), 0.0].into();
+
+        if let Some((_, timer)) = &mut self.sure {
+            *timer -= context.delta_time();
+            if *timer <= 0.0 {
+                self.sure = None;
+            }
+        }
+
+        self.background.on_update(context);
+
+        let mut pos = center - offset * (self.entries.len() as f64 / 2.0 + 1.0);
+        self.back.on_update(context, pos);
+        pos += offset;
+        self.create.on_update(context, pos);
+
+        for (idx, entry) in self.entries.iter_mut().enumerate() {
+            pos += offset;
+            entry.delete.set_text(
+                match self.sure {
+                    Some((sure_idx, _)) if sure_idx == idx => "sure?",
+                    _ => "delete",
+                }
+                .into(),
+            );
+            entry.select.on_update(context, pos);
+            entry.delete.on_update(context, pos + delete_offset);
+        }
+
+        StateTransition::None
+    }
+}
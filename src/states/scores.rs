@@ -1,7 +1,8 @@
 use crate::engine::ui::Button;
 use crate::{
     engine::{event::Event, Context, GameState, StateTransition},
-    states::main_game::TEXT_COLOR,
+    level,
+    states::main_game::{MainGameState, TEXT_COLOR},
     states::main_menu::Background,
     QuantumLoops,
 };
@@ -10,6 +11,8 @@ use crate::{
 pub struct ScoresState {
     background: Background,
     back: Button,
+    // levels with a recorded best run can be clicked to watch it
+    replays: Vec<Option<Button>>,
     scroll: f64,
     limit: f64,
 }
@@ -19,6 +22,7 @@ impl ScoresState {
         Self {
             background: Background::new(),
             back: Button::new(" ← back  ".into()),
+            replays: Vec::new(),
             scroll: 0.0,
             limit: 0.0,
         }
@@ -43,15 +47,25 @@ impl GameState<QuantumLoops> for ScoresState {
             }
             _ => {
                 if self.back.on_event(&event, context) {
-                    StateTransition::Pop
-                } else {
-                    StateTransition::None
+                    return StateTransition::Pop;
+                }
+                for (idx, button) in self.replays.iter_mut().enumerate() {
+                    if let Some(button) = button {
+                        if button.on_event(&event, context) {
+                            if let Some(Some(recording)) = context.storage().best_runs.get(idx) {
+                                return StateTransition::push(MainGameState::replay(
+                                    recording.clone(),
+                                ));
+                            }
+                        }
+                    }
                 }
+                StateTransition::None
             }
         }
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
         self.background.on_update(context);
 
         let size = context.surface().size();
@@ -62,22 +76,38 @@ impl GameState<QuantumLoops> for ScoresState {
         y += off;
         self.back.on_update(context, [x, y].into());
 
-        if let Some(levels) = context.game.levels.borrow_mut().as_ref() {
-            let best_scores = &context.storage().best_scores;
-            let surface = context.surface().context();
+        let entries = context.game.main_pack.borrow().as_ref().map(|pack| {
+            let storage = context.storage();
+            pack.levels
+                .iter()
+                .enumerate()
+                .map(|(idx, level)| {
+                    let best = storage.best_scores.get(idx).copied().unwrap_or_default();
+                    let has_run = matches!(storage.best_runs.get(idx), Some(Some(_)));
+                    let stars = level::star_text(level.stars_for(best));
+                    (format!("{}: {:.2}% {}", level.name, best, stars), has_run)
+                })
+                .collect::<Vec<_>>()
+        });
 
-            for (idx, level) in levels.iter().enumerate() {
-                y += off;
+        if let Some(entries) = entries {
+            self.replays.resize_with(entries.len(), || None);
 
-                let best = best_scores.get(idx).copied().unwrap_or_default();
+            for (idx, (text, has_run)) in entries.iter().enumerate() {
+                y += off;
 
-                surface.set_fill_style(&TEXT_COLOR.into());
-                surface.set_font("2.5rem monospace");
-                surface
-                    .fill_text(&format!("{}: {:.2}%", level.name, best), x, y)
-                    .unwrap();
+                if *has_run {
+                    let button = self.replays[idx].get_or_insert_with(Button::empty);
+                    button.set_text(text.clone().into());
+                    button.on_update(context, [x, y].into());
+                } else {
+                    let surface = context.surface().context();
+                    surface.set_fill_style(&TEXT_COLOR.into());
+                    surface.set_font("2.5rem monospace");
+                    surface.fill_text(text, x, y).unwrap();
+                }
             }
-            self.limit = levels.len() as f64 * off - size.y * 0.25;
+            self.limit = entries.len() as f64 * off - size.y * 0.25;
         }
 
         StateTransition::None
@@ -0,0 +1,115 @@
+use crate::{
+    engine::{event::Event, ui::Button, Context, GameState, StateTransition},
+    states::{main_game::TEXT_COLOR, main_menu::Background},
+    QuantumLoops,
+};
+
+#[derive(Debug)]
+pub struct StatsState {
+    background: Background,
+    back: Button,
+    scroll: f64,
+    limit: f64,
+}
+
+impl StatsState {
+    pub fn new() -> Self {
+        Self {
+            background: Background::new(),
+            back: Button::new(" ← back  ".into()),
+            scroll: 0.0,
+            limit: 0.0,
+        }
+    }
+}
+
+impl GameState<QuantumLoops> for StatsState {
+    fn on_event(
+        &mut self,
+        event: Event,
+        context: &mut Context<QuantumLoops>,
+    ) -> StateTransition<QuantumLoops> {
+        match event {
+            Event::KeyDown { code: 27, .. } => StateTransition::Pop,
+            Event::MouseWheel { delta, .. } => {
+                let yoff = -delta.y * 10.0;
+                let new_scroll = self.scroll - yoff;
+                if new_scroll >= 0.0 && new_scroll < self.limit {
+                    self.scroll = new_scroll;
+                }
+                StateTransition::None
+            }
+            _ => {
+                if self.back.on_event(&event, context) {
+                    return StateTransition::Pop;
+                }
+                StateTransition::None
+            }
+        }
+    }
+
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+        self.background.on_update(context);
+
+        let size = context.surface().size();
+        let x = size.x * 0.5;
+        let title_off = context.rem_to_px(3.0);
+        let line_off = context.rem_to_px(1.5);
+        let mut y = size.y * 0.25 - self.scroll;
+
+        y += title_off;
+        self.back.on_update(context, [x, y].into());
+
+        let entries = context.game.main_pack.borrow().as_ref().map(|pack| {
+            let storage = context.storage();
+            pack.levels
+                .iter()
+                .map(|level| {
+                    let lines = match storage.level_stats(&level.name) {
+                        Some(stats) if stats.attempts > 0 => vec![
+                            format!(
+                                "attempts: {}, wins: {}, losses: {} ({:.0}% won)",
+                                stats.attempts,
+                                stats.wins,
+                                stats.losses,
+                                stats.win_rate()
+                            ),
+                            format!(
+                                "fastest: {}, energy spent: {:.2}",
+                                stats
+                                    .fastest_time
+                                    .map_or_else(|| "-".into(), |t| format!("{:.2}s", t)),
+                                stats.energy_spent
+                            ),
+                            format!(
+                                "disruptions: {}, wrong ring hits: {}",
+                                stats.disruptions, stats.jiggles
+                            ),
+                        ],
+                        _ => vec!["not played yet".into()],
+                    };
+                    (level.name.clone(), lines)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        if let Some(entries) = entries {
+            let start = y;
+            let surface = context.surface().context();
+            surface.set_fill_style(&TEXT_COLOR.into());
+            for (name, lines) in &entries {
+                y += title_off;
+                surface.set_font("2rem monospace");
+                surface.fill_text(name, x, y).unwrap();
+                surface.set_font("1rem monospace");
+                for line in lines {
+                    y += line_off;
+                    surface.fill_text(line, x, y).unwrap();
+                }
+            }
+            self.limit = y - start - size.y * 0.5;
+        }
+
+        StateTransition::None
+    }
+}
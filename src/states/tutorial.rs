@@ -99,7 +99,7 @@ impl GameState<QuantumLoops> for TutorialState {
         StateTransition::None
     }
 
-    fn on_update(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
+    fn on_render(&mut self, context: &mut Context<QuantumLoops>) -> StateTransition<QuantumLoops> {
         self.background.on_update(context);
 
         let center = context.surface().size() / 2.0;
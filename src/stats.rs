@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{Simulation, Status};
+
+/// Everything that happened on a single level over all the attempts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelStats {
+    pub attempts: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub energy_spent: f64,
+    pub fastest_time: Option<f64>,
+    pub jiggles: u32,
+    pub disruptions: u32,
+}
+
+impl LevelStats {
+    /// Adds the outcome of a finished attempt, the attempt itself is counted when it starts
+    pub fn record(&mut self, simulation: &Simulation) {
+        match simulation.status() {
+            Status::Playing => return,
+            Status::Won { .. } => {
+                self.wins += 1;
+                let time = simulation.time();
+                self.fastest_time = Some(self.fastest_time.map_or(time, |f| f.min(time)));
+            }
+            Status::Lost => self.losses += 1,
+        }
+        self.energy_spent += simulation.energy_spent();
+        self.jiggles += simulation.jiggles();
+        self.disruptions += simulation.disruptions();
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.wins as f64 / self.attempts as f64 * 100.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cut, level, SIZE, STEP};
+
+    #[test]
+    fn finished_attempts_are_recorded() {
+        let level = level(100.0, &[(0.1, 10.0), (0.2, 5.0)]);
+        let mut simulation = Simulation::new(level, SIZE.into());
+
+        // the outer ring first is a jiggle, then the inner and the outer for real
+        let inputs = [
+            cut(0.5, 0.55, 195.0, 205.0),
+            cut(1.0, 1.05, 95.0, 105.0),
+            cut(1.5, 1.55, 195.0, 205.0),
+        ];
+        let status = simulation.run(inputs.iter().flatten().cloned(), STEP, 10.0);
+        assert!(matches!(status, Status::Won { .. }));
+
+        let mut stats = LevelStats {
+            attempts: 1,
+            ..Default::default()
+        };
+        stats.record(&simulation);
+        assert_eq!(stats.wins, 1);
+        assert_eq!(stats.disruptions, 3);
+        assert_eq!(stats.jiggles, 1);
+        assert_eq!(stats.fastest_time, Some(simulation.time()));
+        assert!((stats.energy_spent - simulation.energy_spent()).abs() < 1e-9);
+    }
+}
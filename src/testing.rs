@@ -0,0 +1,50 @@
+use nalgebra::Vector2;
+
+use crate::{
+    level::{EnergyRing, GameLevel},
+    simulation::{DisruptionCause, Input, Simulation, TimedInput},
+};
+
+// fixtures shared by the unit tests, the rings are in the middle of the screen
+// of this size, so the relative radius of 0.1 is 100 pixels
+pub const SIZE: [f64; 2] = [1000.0, 1000.0];
+// exact in binary, so that the steps add up to the times in the tests
+pub const STEP: f64 = 1.0 / 64.0;
+
+/// Whole circles around the center, given as the radius and the base energy
+pub fn level(energy: f64, rings: &[(f64, f64)]) -> GameLevel {
+    let rings = rings
+        .iter()
+        .map(|&(radius, base_energy)| EnergyRing::new(Vector2::zeros(), radius, base_energy))
+        .collect();
+    GameLevel::new("a".into(), energy, rings)
+}
+
+/// A radial cut to the right of the center, from `from` to `to` pixels away from it,
+/// started at the `start` time and finished at the `end` one
+pub fn cut(start: f64, end: f64, from: f64, to: f64) -> Vec<TimedInput> {
+    let cause = DisruptionCause::Mouse;
+    vec![
+        TimedInput {
+            time: start,
+            input: Input::Start {
+                pos: [500.0 + from, 500.0].into(),
+                cause,
+            },
+        },
+        TimedInput {
+            time: end,
+            input: Input::Finish {
+                pos: Some([500.0 + to, 500.0].into()),
+                cause,
+            },
+        },
+    ]
+}
+
+/// The same cut, made in an instant without advancing the simulation
+pub fn cut_now(simulation: &mut Simulation, from: f64, to: f64) {
+    for TimedInput { input, .. } in cut(0.0, 0.0, from, to) {
+        simulation.input(input);
+    }
+}